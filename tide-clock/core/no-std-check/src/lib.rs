@@ -0,0 +1,44 @@
+// Touches the frame, the font and the tide model, see Cargo.toml for why this crate exists
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::alloc::{GlobalAlloc, Layout};
+use core::panic::PanicInfo;
+use tide_clock_core::font::Font5;
+use tide_clock_core::tides::TideResponse;
+use tide_clock_core::{MonoFrame, TideModel};
+
+//Never called, it only has to link
+struct NoAlloc;
+
+unsafe impl GlobalAlloc for NoAlloc {
+    unsafe fn alloc(&self, _layout: Layout) -> *mut u8 {
+        core::ptr::null_mut()
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+}
+
+#[global_allocator]
+static ALLOCATOR: NoAlloc = NoAlloc;
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
+
+#[no_mangle]
+pub extern "C" fn tide_clock_smoke(width: u32, height: u32) -> u32 {
+    let frame = MonoFrame::new(width, height);
+    let font = Font5::from_sheet(&frame);
+    let model = TideModel::new(TideResponse {
+        station: String::new(),
+        heights: Vec::new(),
+        extremes: Vec::new(),
+    });
+
+    frame.page_count() + font.faces.len() as u32 + model.get_date_range().is_some() as u32
+}
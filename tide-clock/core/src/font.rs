@@ -0,0 +1,71 @@
+use crate::mono_frame::MonoFrame;
+use alloc::collections::BTreeMap;
+
+// Where each glyph sits on the font sheet, Font-5px.png: (char, x, y, width, height)
+const GLYPHS: &[(char, u32, u32, u32, u32)] = &[
+    (' ', 124, 0, 2, 5),
+    ('_', 124, 0, 1, 5),
+    ('1', 0, 0, 1, 5),
+    ('2', 2, 0, 3, 5),
+    ('3', 6, 0, 3, 5),
+    ('4', 10, 0, 3, 5),
+    ('5', 14, 0, 3, 5),
+    ('6', 18, 0, 3, 5),
+    ('7', 22, 0, 3, 5),
+    ('8', 26, 0, 3, 5),
+    ('9', 30, 0, 3, 5),
+    ('0', 34, 0, 3, 5),
+    (':', 38, 0, 1, 5),
+    ('.', 40, 0, 1, 5),
+    ('m', 42, 0, 5, 5),
+    ('f', 48, 0, 2, 5),
+    ('t', 51, 0, 2, 5),
+    ('!', 54, 0, 1, 5),
+    ('?', 56, 0, 3, 5),
+    ('A', 0, 6, 3, 5),
+    ('B', 4, 6, 3, 5),
+    ('C', 8, 6, 3, 5),
+    ('D', 12, 6, 3, 5),
+    ('E', 16, 6, 3, 5),
+    ('F', 20, 6, 3, 5),
+    ('G', 24, 6, 3, 5),
+    ('H', 28, 6, 3, 5),
+    ('I', 32, 6, 1, 5),
+    ('J', 34, 6, 3, 5),
+    ('K', 38, 6, 3, 5),
+    ('L', 42, 6, 3, 5),
+    ('M', 46, 6, 5, 5),
+    ('N', 52, 6, 3, 5),
+    ('O', 56, 6, 3, 5),
+    ('P', 60, 6, 3, 5),
+    ('Q', 64, 6, 4, 5),
+    ('R', 69, 6, 3, 5),
+    ('S', 73, 6, 3, 5),
+    ('T', 77, 6, 3, 5),
+    ('U', 81, 6, 3, 5),
+    ('V', 85, 6, 3, 5),
+    ('W', 89, 6, 5, 5),
+    ('X', 95, 6, 3, 5),
+    ('Y', 99, 6, 3, 5),
+    ('Z', 103, 6, 3, 5),
+    ('(', 0, 12, 6, 6),
+    (')', 7, 12, 6, 6),
+    ('[', 14, 12, 6, 6),
+    (']', 21, 12, 6, 6),
+];
+
+pub struct Font5 {
+    pub faces: BTreeMap<char, MonoFrame>,
+}
+
+impl Font5 {
+    // Cuts the glyphs out of the font sheet, however it was loaded
+    pub fn from_sheet(sheet: &MonoFrame) -> Font5 {
+        let faces = GLYPHS
+            .iter()
+            .map(|&(c, x, y, w, h)| (c, sheet.crop(x, y, w, h)))
+            .collect();
+
+        Font5 { faces }
+    }
+}
@@ -0,0 +1,16 @@
+// What the face is made of, without std: the tide model, the 1 bit frame and the painters
+// that draw on it. Times are UTC, anything shown in local time takes its offset from the
+// caller. The tide-clock crate loads the data and fonts and drives the render devices
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod font;
+pub mod maths;
+pub mod mono_frame;
+pub mod paint;
+pub mod tides;
+
+pub use mono_frame::MonoFrame;
+pub use paint::Painter;
+pub use tides::TideModel;
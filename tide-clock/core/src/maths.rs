@@ -4,7 +4,7 @@ pub fn lerp(t: f32, min: i32, max: i32) -> i32 {
 
     let value = t01 * range + min as f32;
 
-    value.round() as i32
+    libm::roundf(value) as i32
 }
 
 pub fn inverse_lerp(value: f32, min: f32, max: f32) -> f32 {
@@ -0,0 +1,380 @@
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "image")]
+use image::{Rgb, RgbImage};
+
+#[cfg(feature = "image")]
+const PIXEL_WHITE: Rgb<u8> = Rgb([255_u8, 255_u8, 255_u8]);
+#[cfg(feature = "image")]
+const PIXEL_BLACK: Rgb<u8> = Rgb([0_u8, 0_u8, 0_u8]);
+
+// A 1 bit frame buffer. Pixels are packed the same way the SSD13xx family stores them in
+// display RAM: the screen is split into pages of 8 rows, each page holds one byte per column
+// and bit 0 is the top row of the page. Render devices for those panels can send pages as is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MonoFrame {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl MonoFrame {
+    pub fn new(width: u32, height: u32) -> MonoFrame {
+        let pages = height.div_ceil(8);
+
+        MonoFrame {
+            width,
+            height,
+            data: vec![0; (width * pages) as usize],
+        }
+    }
+
+    pub fn from_fn<F>(width: u32, height: u32, mut f: F) -> MonoFrame
+    where
+        F: FnMut(u32, u32) -> bool,
+    {
+        let mut frame = MonoFrame::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                frame.set_pixel(x, y, f(x, y));
+            }
+        }
+
+        frame
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.height.div_ceil(8)
+    }
+
+    // One byte per column for the given page, see the layout notes above
+    pub fn page(&self, page: u32) -> &[u8] {
+        let start = (page * self.width) as usize;
+        &self.data[start..start + self.width as usize]
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    // Out of bounds pixels read as off
+    pub fn get_pixel(&self, x: u32, y: u32) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+
+        self.data[self.index(x, y)] & (1 << (y % 8)) != 0
+    }
+
+    // Out of bounds pixels are clipped
+    pub fn set_pixel(&mut self, x: u32, y: u32, on: bool) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let index = self.index(x, y);
+        if on {
+            self.data[index] |= 1 << (y % 8);
+        } else {
+            self.data[index] &= !(1 << (y % 8));
+        }
+    }
+
+    pub fn fill(&mut self, on: bool) {
+        let value = if on { 0xFF } else { 0x00 };
+        for byte in self.data.iter_mut() {
+            *byte = value;
+        }
+        self.clear_padding();
+    }
+
+    pub fn invert(&mut self) {
+        for byte in self.data.iter_mut() {
+            *byte = !*byte;
+        }
+        self.clear_padding();
+    }
+
+    // Bresenham, end points inclusive. Accepts coordinates off screen, those pixels are clipped
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, on: bool) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+
+        let (mut x, mut y) = (x0, y0);
+        let mut err = dx + dy;
+        loop {
+            if x >= 0 && y >= 0 {
+                self.set_pixel(x as u32, y as u32, on);
+            }
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, on: bool) {
+        for row in y..y.saturating_add(h).min(self.height) {
+            for col in x..x.saturating_add(w).min(self.width) {
+                self.set_pixel(col, row, on);
+            }
+        }
+    }
+
+    pub fn rect(&mut self, x: u32, y: u32, w: u32, h: u32, on: bool) {
+        if w == 0 || h == 0 {
+            return;
+        }
+
+        let (x, y) = (x as i32, y as i32);
+        let (right, bottom) = (x + w as i32 - 1, y + h as i32 - 1);
+
+        self.line(x, y, right, y, on);
+        self.line(x, bottom, right, bottom, on);
+        self.line(x, y, x, bottom, on);
+        self.line(right, y, right, bottom, on);
+    }
+
+    // Copies every pixel of `src`, lit or not, with its top left corner at x, y
+    pub fn blit(&mut self, src: &MonoFrame, x: u32, y: u32) {
+        for sy in 0..src.height {
+            for sx in 0..src.width {
+                self.set_pixel(x + sx, y + sy, src.get_pixel(sx, sy));
+            }
+        }
+    }
+
+    // Smallest rectangle holding every lit pixel as (left, top, right, bottom), inclusive.
+    // None for a blank frame
+    pub fn lit_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if !self.get_pixel(x, y) {
+                    continue;
+                }
+
+                bounds = Some(match bounds {
+                    None => (x, y, x, y),
+                    Some((l, t, r, b)) => (l.min(x), t.min(y), r.max(x), b.max(y)),
+                });
+            }
+        }
+
+        bounds
+    }
+
+    // Moves the picture by dx, dy. Pixels pushed off the edge are lost, uncovered ones are off
+    pub fn shifted(&self, dx: i32, dy: i32) -> MonoFrame {
+        MonoFrame::from_fn(self.width, self.height, |x, y| {
+            let (sx, sy) = (x as i32 - dx, y as i32 - dy);
+            sx >= 0 && sy >= 0 && self.get_pixel(sx as u32, sy as u32)
+        })
+    }
+
+    // The w x h rectangle with its top left corner at x, y. Off the edge reads as off
+    pub fn crop(&self, x: u32, y: u32, w: u32, h: u32) -> MonoFrame {
+        MonoFrame::from_fn(w, h, |cx, cy| self.get_pixel(x + cx, y + cy))
+    }
+
+    // Pixels are lit where the red channel is non zero, which is how the panel was driven
+    // when frames were still drawn as RGB
+    #[cfg(feature = "image")]
+    pub fn from_rgb(img: &RgbImage) -> MonoFrame {
+        MonoFrame::from_fn(img.width(), img.height(), |x, y| img.get_pixel(x, y)[0] > 0)
+    }
+
+    #[cfg(feature = "image")]
+    pub fn to_rgb(&self) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| match self.get_pixel(x, y) {
+            true => PIXEL_WHITE,
+            false => PIXEL_BLACK,
+        })
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (x + (y / 8) * self.width) as usize
+    }
+
+    // Rows past the bottom of a partial last page must stay off, otherwise equal looking
+    // frames would compare as different
+    fn clear_padding(&mut self) {
+        let rows = self.height % 8;
+        if rows == 0 {
+            return;
+        }
+
+        let mask = (1_u8 << rows) - 1;
+        let start = ((self.page_count() - 1) * self.width) as usize;
+        for byte in self.data[start..].iter_mut() {
+            *byte &= mask;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixels_are_page_packed() {
+        let mut frame = MonoFrame::new(4, 16);
+        frame.set_pixel(1, 0, true);
+        frame.set_pixel(1, 7, true);
+        frame.set_pixel(2, 9, true);
+
+        assert_eq!(frame.page_count(), 2);
+        assert_eq!(frame.page(0), &[0, 0b1000_0001, 0, 0]);
+        assert_eq!(frame.page(1), &[0, 0, 0b0000_0010, 0]);
+        assert!(frame.get_pixel(1, 7));
+        assert!(!frame.get_pixel(1, 6));
+
+        frame.set_pixel(1, 7, false);
+        assert_eq!(frame.page(0), &[0, 0b0000_0001, 0, 0]);
+    }
+
+    #[test]
+    fn test_out_of_bounds_is_clipped() {
+        let mut frame = MonoFrame::new(3, 3);
+        frame.set_pixel(3, 0, true);
+        frame.set_pixel(0, 3, true);
+
+        assert_eq!(frame, MonoFrame::new(3, 3));
+        assert!(!frame.get_pixel(100, 100));
+    }
+
+    #[test]
+    fn test_partial_page() {
+        let mut frame = MonoFrame::new(2, 10);
+        frame.fill(true);
+
+        assert_eq!(frame.data().len(), 4);
+        assert!(frame.get_pixel(1, 9));
+        assert_eq!(frame.page(1), &[0b11, 0b11]);
+
+        frame.invert();
+        assert_eq!(frame, MonoFrame::new(2, 10));
+    }
+
+    #[test]
+    fn test_line() {
+        let mut frame = MonoFrame::new(4, 4);
+        frame.line(0, 0, 3, 3, true);
+        frame.line(-2, 3, 0, 3, true);
+
+        let lit: Vec<(u32, u32)> = (0..4)
+            .flat_map(|y| (0..4).map(move |x| (x, y)))
+            .filter(|&(x, y)| frame.get_pixel(x, y))
+            .collect();
+        assert_eq!(lit, vec![(0, 0), (1, 1), (2, 2), (0, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn test_rect() {
+        let mut outline = MonoFrame::new(4, 4);
+        outline.rect(0, 0, 4, 4, true);
+        let mut filled = MonoFrame::new(4, 4);
+        filled.fill_rect(0, 0, 4, 4, true);
+
+        assert!(!outline.get_pixel(1, 1));
+        assert!(outline.get_pixel(3, 2));
+
+        outline.fill_rect(1, 1, 2, 2, true);
+        assert_eq!(outline, filled);
+
+        filled.fill_rect(2, 2, 100, 100, false);
+        assert!(!filled.get_pixel(3, 3));
+        assert!(filled.get_pixel(1, 3));
+    }
+
+    #[test]
+    fn test_blit_copies_lit_and_unlit_pixels() {
+        let mut frame = MonoFrame::new(4, 4);
+        frame.fill(true);
+        let glyph = MonoFrame::from_fn(2, 2, |x, y| x == y);
+
+        frame.blit(&glyph, 3, 1);
+
+        assert!(frame.get_pixel(3, 1));
+        assert!(!frame.get_pixel(3, 2));
+        assert!(frame.get_pixel(2, 2));
+    }
+
+    #[test]
+    fn test_invert() {
+        let mut frame = MonoFrame::new(2, 2);
+        frame.set_pixel(0, 0, true);
+        frame.invert();
+
+        assert!(!frame.get_pixel(0, 0));
+        assert!(frame.get_pixel(1, 1));
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_rgb_round_trip() {
+        let frame = MonoFrame::from_fn(5, 9, |x, y| (x + y) % 3 == 0);
+        let img = frame.to_rgb();
+
+        assert_eq!(img.get_pixel(0, 0), &PIXEL_WHITE);
+        assert_eq!(img.get_pixel(1, 0), &PIXEL_BLACK);
+        assert_eq!(MonoFrame::from_rgb(&img), frame);
+    }
+
+    #[test]
+    fn test_crop() {
+        let frame = MonoFrame::from_fn(4, 4, |x, y| x == y);
+
+        assert_eq!(
+            frame.crop(1, 0, 2, 2),
+            MonoFrame::from_fn(2, 2, |x, y| x + 1 == y)
+        );
+        assert_eq!(
+            frame.crop(3, 3, 2, 1),
+            MonoFrame::from_fn(2, 1, |x, _| x == 0)
+        );
+    }
+
+    #[test]
+    fn test_lit_bounds() {
+        let mut frame = MonoFrame::new(8, 8);
+        assert_eq!(frame.lit_bounds(), None);
+
+        frame.set_pixel(2, 5, true);
+        frame.set_pixel(6, 1, true);
+        assert_eq!(frame.lit_bounds(), Some((2, 1, 6, 5)));
+    }
+
+    #[test]
+    fn test_shifted() {
+        let frame = MonoFrame::from_fn(3, 3, |x, y| x == 0 && y == 2);
+
+        assert_eq!(
+            frame.shifted(1, -1),
+            MonoFrame::from_fn(3, 3, |x, y| x == 1 && y == 1)
+        );
+        assert_eq!(frame.shifted(-1, 0), MonoFrame::new(3, 3));
+    }
+}
@@ -0,0 +1,601 @@
+use crate::font::Font5;
+use crate::maths;
+use crate::mono_frame::MonoFrame;
+use crate::tides::{TideExtremeGraphData, TideModel, TideModelWindow};
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use core::cmp::max;
+use serde::Deserialize;
+
+const PIXEL_WHITE: bool = true;
+const PIXEL_BLACK: bool = false;
+
+// 0 | 1 | 0
+// 1 | 1 | 1
+// 1 | 1 | 1
+const FLOOD_FILL_MASK: [u32; 9] = [0, 1, 0, 1, 1, 1, 1, 1, 1];
+
+pub struct Position {
+    pub x: u32,
+    pub y: u32,
+}
+
+pub struct Bounds {
+    w: u32,
+    h: u32,
+}
+
+pub trait Painter {
+    fn paint(&self, buffer: &mut MonoFrame, now: DateTime<Utc>);
+
+    //The next time after `now` the painter would draw something different, None if it only
+    //changes when it's rebuilt with different content
+    fn next_change(&self, _now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        None
+    }
+}
+
+// For painters built from the time, like the clock's text. They can't tell by themselves
+// when they go out of date, so whoever builds them does
+pub struct ChangesAt<P: Painter> {
+    at: Option<DateTime<Utc>>,
+    painter: P,
+}
+
+impl<P: Painter> ChangesAt<P> {
+    pub fn new(at: Option<DateTime<Utc>>, painter: P) -> ChangesAt<P> {
+        ChangesAt { at, painter }
+    }
+}
+
+impl<P: Painter> Painter for ChangesAt<P> {
+    fn paint(&self, buffer: &mut MonoFrame, now: DateTime<Utc>) {
+        self.painter.paint(buffer, now);
+    }
+
+    fn next_change(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let inner = self.painter.next_change(now);
+        self.at.into_iter().chain(inner).min()
+    }
+}
+
+pub struct TextField<'a> {
+    text: String,
+    pos: Position,
+    bounds: Bounds,
+    font: &'a Font5,
+}
+
+impl TextField<'_> {
+    pub fn new(text: String, font: &Font5, x: u32, y: u32) -> TextField<'_> {
+        let mut tf = TextField {
+            text: String::new(),
+            font,
+            pos: Position { x, y },
+            bounds: Bounds { w: 0, h: 0 },
+        };
+
+        tf.set_text(text); //Update bounds
+
+        tf
+    }
+
+    pub fn set_text(&mut self, text: String) {
+        self.text = text;
+
+        let mut width = 0;
+        let mut height: u32 = 0;
+        for c in self.text.chars() {
+            if let Some(si) = self.font.faces.get(&c) {
+                width += si.width() + 1;
+                height = max(height, si.height());
+            }
+        }
+
+        self.bounds = Bounds {
+            w: width,
+            h: height,
+        };
+    }
+}
+
+impl Painter for TextField<'_> {
+    fn paint(&self, buffer: &mut MonoFrame, _now: DateTime<Utc>) {
+        //bounds check
+
+        let mut caret = 0;
+        for c in self.text.chars() {
+            //println!("{}", c);
+
+            if let Some(si) = self.font.faces.get(&c) {
+                buffer.blit(si, self.pos.x + caret, self.pos.y);
+                caret += si.width() + 1;
+            }
+        }
+    }
+}
+
+// Paints a child as though it were a different time, e.g. to show tomorrow's tides
+pub struct TimeShift<P: Painter> {
+    offset: Duration,
+    painter: P,
+}
+
+impl<P: Painter> TimeShift<P> {
+    pub fn new(offset: Duration, painter: P) -> TimeShift<P> {
+        TimeShift { offset, painter }
+    }
+}
+
+impl<P: Painter> Painter for TimeShift<P> {
+    fn paint(&self, buffer: &mut MonoFrame, now: DateTime<Utc>) {
+        let shifted = now.checked_add_signed(self.offset).unwrap_or(now);
+        self.painter.paint(buffer, shifted);
+    }
+
+    fn next_change(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let shifted = now.checked_add_signed(self.offset)?;
+        let change = self.painter.next_change(shifted)?;
+        change.checked_sub_signed(self.offset)
+    }
+}
+
+// A column of text fields, one per line
+pub struct TextList<'a> {
+    lines: Vec<TextField<'a>>,
+}
+
+impl TextList<'_> {
+    pub fn new(lines: Vec<String>, font: &Font5, x: u32, y: u32, line_height: u32) -> TextList<'_> {
+        let lines = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, text)| TextField::new(text, font, x, y + i as u32 * line_height))
+            .collect();
+
+        TextList { lines }
+    }
+}
+
+impl Painter for TextList<'_> {
+    fn paint(&self, buffer: &mut MonoFrame, now: DateTime<Utc>) {
+        for line in self.lines.iter() {
+            line.paint(buffer, now);
+        }
+    }
+}
+
+pub struct GraphCanvas<'a> {
+    pos: Position,
+    bounds: Bounds,
+    data: TideModelWindow<'a>,
+    font: &'a Font5,
+    //For the times on the labels
+    offset: FixedOffset,
+}
+
+impl GraphCanvas<'_> {
+    pub fn new<'a>(
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+        data: TideModelWindow<'a>,
+        font: &'a Font5,
+        offset: FixedOffset,
+    ) -> GraphCanvas<'a> {
+        GraphCanvas {
+            pos: Position { x, y },
+            bounds: Bounds { w, h },
+            data,
+            font,
+            offset,
+        }
+    }
+}
+
+impl Painter for GraphCanvas<'_> {
+    //The play head moves on to the next sample
+    fn next_change(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        TideModel::next_index_change(self.data.dates, now)
+    }
+
+    fn paint(&self, buffer: &mut MonoFrame, now: DateTime<Utc>) {
+        for col in 0..self.bounds.w {
+            for row in 0..self.bounds.h {
+                let raw = calculate_pixel(
+                    self.data.normalised_heights,
+                    &self.bounds,
+                    col as i32,
+                    row as i32,
+                );
+
+                let pixel = match raw {
+                    0 => PIXEL_BLACK,
+                    1 => PIXEL_WHITE,
+                    _ => PIXEL_BLACK,
+                };
+
+                buffer.set_pixel(self.pos.x + col, self.pos.y + row, pixel);
+            }
+        }
+
+        //Race condition: labels depend on drawn wave data to draw descenders
+        for data_point in self.data.extremes().iter() {
+            let data_index_in_window = self.data.get_extreme_index_in_window(data_point.index());
+            let label = ExtremeLabel::new(
+                self.font,
+                data_point,
+                data_index_in_window,
+                &self.pos,
+                self.offset,
+            );
+            label.paint(buffer, now);
+        }
+
+        // Draw play head
+        let mut current_index: u32 = 0;
+        //let now = Utc.ymd(2020, 9, 14).and_hms(9, 39, 00);
+        if let Some(index) = TideModel::find_time_index(self.data.dates, now) {
+            let x = self.pos.x + index;
+            current_index = index; //record result
+
+            //Draw play head, only as far down as the graph goes
+            if x < buffer.width() {
+                for y in self.pos.y..(self.pos.y + self.bounds.h).min(buffer.height()) {
+                    let px = match y % 2 {
+                        0 => PIXEL_WHITE,
+                        1 => PIXEL_BLACK,
+                        _ => PIXEL_BLACK,
+                    };
+
+                    buffer.set_pixel(self.pos.x + index, y, px);
+                }
+            }
+        }
+
+        // Flood fill erase to remove waves in the past
+        for col in 0..current_index {
+            for row in 0..self.bounds.h {
+                let x = col as i32;
+                let y = row as i32; //Invert y axis
+
+                let kernel: [u32; 9] = [
+                    calculate_pixel(self.data.normalised_heights, &self.bounds, x - 1, y - 1),
+                    calculate_pixel(self.data.normalised_heights, &self.bounds, x, y - 1),
+                    calculate_pixel(self.data.normalised_heights, &self.bounds, x + 1, y - 1),
+                    calculate_pixel(self.data.normalised_heights, &self.bounds, x - 1, y),
+                    calculate_pixel(self.data.normalised_heights, &self.bounds, x, y),
+                    calculate_pixel(self.data.normalised_heights, &self.bounds, x + 1, y),
+                    calculate_pixel(self.data.normalised_heights, &self.bounds, x - 1, y + 1),
+                    calculate_pixel(self.data.normalised_heights, &self.bounds, x, y + 1),
+                    calculate_pixel(self.data.normalised_heights, &self.bounds, x + 1, y + 1),
+                ];
+
+                if should_erase(kernel, &FLOOD_FILL_MASK) {
+                    let screen_x = self.pos.x + col;
+                    let screen_y = self.pos.y + row;
+
+                    if screen_x >= buffer.width() || screen_y >= buffer.height() {
+                        continue;
+                    }
+
+                    buffer.set_pixel(screen_x, screen_y, PIXEL_BLACK);
+                }
+            }
+        }
+    }
+}
+
+fn calculate_pixel(normalized_heights: &[f32], bounds: &Bounds, x: i32, y: i32) -> u32 {
+    if x < 0 || x >= normalized_heights.len() as i32 {
+        return 1;
+    }
+
+    let height = match normalized_heights.get(x as usize) {
+        Some(h) => h.to_owned(),
+        None => 0_f32,
+    };
+
+    let px_height = maths::lerp(height, 0, bounds.h as i32) as u32;
+    let y_pos = bounds.h.saturating_sub(px_height);
+
+    if y >= y_pos as i32 {
+        return 1;
+    }
+
+    0
+}
+
+fn should_erase(kernel: [u32; 9], mask: &[u32; 9]) -> bool {
+    // kernel        mask         result (fails)
+    // 0 | 0 | 0     0 | 1 | 0    0 | 0 | 0
+    // 1 | 1 | 1  ?  1 | 1 | 1  = 1 | 1 | 1
+    // 1 | 1 | 1     1 | 1 | 1    1 | 1 | 1
+
+    for i in 0..9 {
+        let result = kernel[i] & mask[i];
+
+        if result ^ mask[i] == 1 {
+            return false;
+        }
+    }
+    true
+}
+
+pub struct ExtremeLabel<'a> {
+    text_field: TextField<'a>,
+}
+
+impl ExtremeLabel<'_> {
+    pub fn new<'a>(
+        font: &'a Font5,
+        data: &'a TideExtremeGraphData,
+        data_index: u32,
+        canvas_pos: &Position,
+        offset: FixedOffset,
+    ) -> ExtremeLabel<'a> {
+        let pos = Position {
+            x: canvas_pos.x + data_index,
+            y: 0,
+        };
+
+        let local_dt = data.date().with_timezone(&offset);
+
+        ExtremeLabel {
+            text_field: TextField::new(local_dt.format("%H:%M").to_string(), font, pos.x, pos.y),
+        }
+    }
+}
+
+impl Painter for ExtremeLabel<'_> {
+    fn paint(&self, buffer: &mut MonoFrame, now: DateTime<Utc>) {
+        self.text_field.paint(buffer, now);
+
+        let baseline = self.text_field.pos.y + self.text_field.bounds.h + 2_u32;
+
+        //Draw underline
+        for i in 0..self.text_field.bounds.w.saturating_sub(1) {
+            let x = self.text_field.pos.x + i;
+            let y = baseline;
+
+            buffer.set_pixel(x, y, PIXEL_WHITE);
+        }
+
+        // Draw descenders
+        let x = self.text_field.pos.x;
+        let mut highest: u32 = 0;
+        if x < buffer.width() {
+            //Find highest wave pixel
+            for y in (0..buffer.height()).rev() {
+                //When we find an unlit pixel, bail and set as highest.
+                if !buffer.get_pixel(x, y) {
+                    highest = y;
+                    break;
+                }
+            }
+
+            //Draw from a baseline to highest one above highest for 1px gap. This may be eq or above the base line, in which case nothing gets drawn.
+            //A graph lit right to the top of the screen leaves highest at 0
+            for y in baseline..highest.saturating_sub(1) {
+                buffer.set_pixel(x, y, PIXEL_WHITE);
+            }
+        }
+    }
+}
+
+pub struct WaterMark<'a> {
+    pos: Position,
+    bounds: Bounds,
+    tide_model: &'a TideModel,
+}
+
+impl WaterMark<'_> {
+    pub fn new(x: u32, y: u32, w: u32, h: u32, tide_model: &TideModel) -> WaterMark<'_> {
+        WaterMark {
+            pos: Position { x, y },
+            bounds: Bounds { w, h },
+            tide_model,
+        }
+    }
+}
+
+impl Painter for WaterMark<'_> {
+    fn next_change(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.tide_model.next_sample_change(now)
+    }
+
+    fn paint(&self, buffer: &mut MonoFrame, now: DateTime<Utc>) {
+        let bottom = self.pos.y + self.bounds.h.saturating_sub(1);
+
+        //Draw upper + lower notch
+        buffer.set_pixel(self.pos.x, self.pos.y, PIXEL_WHITE);
+        buffer.set_pixel(self.pos.x, bottom, PIXEL_WHITE);
+
+        //Draw bar
+        for row in 0..self.bounds.h {
+            buffer.set_pixel(self.pos.x + 1_u32, self.pos.y + row, PIXEL_WHITE);
+        }
+
+        //Draw water mark
+        let t = self.tide_model.get_current_norm_height(now);
+        let y_pos: u32 = maths::lerp(t, bottom as i32, self.pos.y as i32) as u32;
+
+        let mark_y = y_pos;
+        let mark_x: u32 = match mark_y == self.pos.y || mark_y == bottom {
+            //Offset by 1 pixel if at upper or lower notch, on the notch at the left edge
+            true => self.pos.x.saturating_sub(1),
+            false => self.pos.x,
+        };
+
+        buffer.set_pixel(mark_x, mark_y, PIXEL_WHITE);
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Copy, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+    #[default]
+    None,
+    //Next page pushes the current one out to the left
+    Slide,
+    //Next page is revealed left to right over the current one
+    Wipe,
+}
+
+// Blend two frames of the same size, progress runs from 0 (all `from`) to 1 (all `to`)
+pub fn transition_frame(
+    from: &MonoFrame,
+    to: &MonoFrame,
+    transition: Transition,
+    progress: f32,
+) -> MonoFrame {
+    let (width, height) = to.dimensions();
+    let split = maths::lerp(progress, 0, width as i32) as u32;
+
+    match transition {
+        Transition::None => to.clone(),
+        Transition::Slide => MonoFrame::from_fn(width, height, |x, y| {
+            //Columns left of the seam are the tail of `from`, the rest the head of `to`
+            if x + split < width {
+                from.get_pixel(x + split, y)
+            } else {
+                to.get_pixel(x + split - width, y)
+            }
+        }),
+        Transition::Wipe => MonoFrame::from_fn(width, height, |x, y| {
+            if x < split {
+                to.get_pixel(x, y)
+            } else {
+                from.get_pixel(x, y)
+            }
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_erase() {
+        let kernel = [0, 1, 1, 1, 1, 1, 1, 1, 1];
+
+        assert!(should_erase(kernel, &FLOOD_FILL_MASK));
+
+        let kernel = [1, 1, 0, 1, 1, 1, 1, 1, 1];
+        assert!(should_erase(kernel, &FLOOD_FILL_MASK));
+    }
+
+    #[test]
+    fn test_calculate_pixel() {
+        // 0 0 0 1
+        // 0 0 1 1
+        // 0 1 1 1
+        // 1 1 1 1
+        let normalized_heights: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
+        let bounds = Bounds { w: 4, h: 4 };
+
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 0), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 0), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 2, 0), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 3, 0), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 1), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 1), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 2, 1), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 3, 1), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 2), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 2), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 2, 2), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 3, 2), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 3), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 3), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 2, 3), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 3, 3), 1);
+
+        // 0 0 0 0 0 1
+        // 0 0 0 0 0 1
+        // 0 0 0 0 1 1
+        // 0 0 0 0 1 1
+        // 0 0 0 1 1 1
+        // 0 0 0 1 1 1
+        // 0 0 1 1 1 1
+        // 0 0 1 1 1 1
+        // 0 1 1 1 1 1
+        // 0 1 1 1 1 1
+        let normalized_heights: [f32; 6] = [0_f32, 0.2, 0.4, 0.6, 0.8, 1_f32];
+        let bounds = Bounds { w: 6, h: 10 };
+
+        // x 0 0 0 0 0 1
+        // x 0 0 0 0 0 1
+        // x 0 0 0 0 1 1
+        // x 0 0 0 0 1 1
+        // x 0 0 0 1 1 1
+        // x 0 0 0 1 1 1
+        // x 0 0 1 1 1 1
+        // x 0 0 1 1 1 1
+        //|x 0 1|1 1 1 1
+        //|x 0 1|1 1 1 1
+        //|x x x|x x x x
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, -1, 8), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 8), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 8), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, -1, 9), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 9), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 9), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, -1, 10), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 10), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 10), 1);
+
+        // x x x x x x x x
+        // 0 0 0 0|0 1 x|x
+        // 0 0 0 0|0 1 x|x
+        // 0 0 0 0|1 1 x|x
+        // 0 0 0 0 1 1 x x
+        // 0 0 0 1 1 1 x x
+        // 0 0 0 1 1 1 x x
+        // 0 0 1 1 1 1 x x
+        // 0 0 1 1 1 1 x x
+        // 0 1 1 1 1 1 x x
+        // 0 1 1 1 1 1 x x
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 4, 0), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 5, 0), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 6, 0), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 4, 1), 0);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 5, 1), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 6, 1), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 4, 2), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 5, 2), 1);
+        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 6, 2), 1);
+    }
+
+    #[test]
+    fn test_transition_frame() {
+        let mut from = MonoFrame::new(4, 1);
+        from.fill(PIXEL_WHITE);
+        let to = MonoFrame::from_fn(4, 1, |x, _| x == 0);
+
+        let halfway = transition_frame(&from, &to, Transition::Slide, 0.5);
+        let expected = [PIXEL_WHITE, PIXEL_WHITE, PIXEL_WHITE, PIXEL_BLACK];
+        assert_eq!(
+            halfway,
+            MonoFrame::from_fn(4, 1, |x, _| expected[x as usize])
+        );
+
+        let halfway = transition_frame(&from, &to, Transition::Wipe, 0.5);
+        let expected = [PIXEL_WHITE, PIXEL_BLACK, PIXEL_WHITE, PIXEL_WHITE];
+        assert_eq!(
+            halfway,
+            MonoFrame::from_fn(4, 1, |x, _| expected[x as usize])
+        );
+
+        for transition in &[Transition::None, Transition::Slide, Transition::Wipe] {
+            assert_eq!(transition_frame(&from, &to, *transition, 1.0), to);
+        }
+        assert_eq!(transition_frame(&from, &to, Transition::Slide, 0.0), from);
+    }
+}
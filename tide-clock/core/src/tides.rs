@@ -0,0 +1,393 @@
+use crate::maths;
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+pub struct TideModel {
+    water_mark: WaterMarkData,
+    heights: Vec<f32>,
+    normalised_heights: Vec<f32>,
+    dates: Vec<DateTime<Utc>>,
+    extremes: Vec<TideExtremeGraphData>,
+}
+
+pub enum DataFreshness {
+    Fresh,
+    NeedsUpdate,
+}
+
+#[derive(Copy, Clone)]
+pub struct TideModelWindow<'a> {
+    pub water_mark: WaterMarkData,
+    pub normalised_heights: &'a [f32],
+    pub dates: &'a [DateTime<Utc>],
+    extremes: &'a [TideExtremeGraphData],
+    start_index: u32,
+}
+
+impl TideModel {
+    pub fn new(data: TideResponse) -> TideModel {
+        let water_mark = TideModel::get_water_mark(&data.heights);
+
+        //Iterate over all heights
+        //Transform the height to a nomralised value
+        //Collect (aka allocate) into a new collection
+        let normalised_heights: Vec<f32> = data
+            .heights
+            .iter()
+            .map(|h| maths::inverse_lerp(h.height, water_mark.low_water, water_mark.high_water))
+            .collect();
+
+        let heights = data.heights.iter().map(|h| h.height).collect();
+        let dates = data.heights.iter().map(|h| h.date).collect();
+
+        let mut extremes: Vec<TideExtremeGraphData> = vec![];
+
+        for extreme in data.extremes.iter() {
+            //Iterate over all heights
+            //Enumerate them to preserve the index (index:usize, height:TideHeightData)
+            //Find the height.dt closest to extreme.dt - min_by_key returns smallest delta
+            let option = data
+                .heights
+                .iter()
+                .enumerate()
+                .min_by_key(|kvp| kvp.1.dt.abs_diff(extreme.dt));
+
+            //If Some, returns the iterator tuple (index:usize, height:TideHeightData)
+            if let Some((index, _height_data)) = option {
+                extremes.push(TideExtremeGraphData {
+                    index: index as u32,
+                    date: extreme.date,
+                    height: extreme.height,
+                    kind: ExtremeKind::from_api(&extreme.extreme_type),
+                });
+            }
+        }
+
+        TideModel {
+            water_mark,
+            heights,
+            normalised_heights,
+            extremes,
+            dates,
+        }
+    }
+
+    pub fn get_window<Tz: TimeZone>(
+        &self,
+        now: DateTime<Tz>,
+        graph_width: u32,
+    ) -> (TideModelWindow<'_>, DataFreshness) {
+        let now = now.with_timezone(&Utc);
+        //Only fails at the very start of time, where there's no tide data anyway
+        let start_utc = now.checked_sub_signed(Duration::hours(8)).unwrap_or(now);
+
+        let mut freshness = DataFreshness::Fresh;
+        let mut start: usize = 0;
+        match TideModel::find_time_index(&self.dates, start_utc) {
+            Some(start_index) => {
+                start = start_index as usize;
+                //Do we have enough values to draw the graph? Width of the graph + 5 frames
+                if self.normalised_heights.len() - start < graph_width as usize + 5 {
+                    freshness = DataFreshness::NeedsUpdate;
+                }
+            }
+            None => freshness = DataFreshness::NeedsUpdate,
+        };
+
+        (
+            TideModelWindow {
+                water_mark: self.water_mark,
+                normalised_heights: &self.normalised_heights[start..],
+                dates: &self.dates[start..],
+                extremes: &self.extremes,
+                start_index: start as u32,
+            },
+            freshness,
+        )
+    }
+
+    fn get_water_mark(heights: &[TideHeightData]) -> WaterMarkData {
+        //Need to use ordered float as the default doesn't implement Ord
+        let high_water = match heights.iter().map(|h| OrderedFloat::from(h.height)).max() {
+            Some(ordered) => ordered.into_inner(),
+            None => 0_f32,
+        };
+
+        //Need to use ordered float as the default doesn't implement Ord
+        let low_water = match heights.iter().map(|h| OrderedFloat::from(h.height)).min() {
+            Some(ordered) => ordered.into_inner(),
+            None => 0_f32,
+        };
+
+        WaterMarkData {
+            high_water,
+            low_water,
+            current_water: 2.0,
+        }
+    }
+
+    pub fn find_time_index(dates: &[DateTime<Utc>], now: DateTime<Utc>) -> Option<u32> {
+        //For each date in dates
+        //Enumerate as (index, date)
+        //Find the entry whos dateTime is closest to now
+        let result = dates.iter().enumerate().min_by_key(|kvp| {
+            let delta = now.signed_duration_since(kvp.1.to_owned());
+            delta.num_seconds().abs()
+        });
+
+        if let Some(kvp) = result {
+            return Some(kvp.0 as u32);
+        }
+
+        None
+    }
+
+    // When find_time_index will next return a different index, which is just past the halfway
+    // point to the next sample. None after the last one
+    pub fn next_index_change(dates: &[DateTime<Utc>], now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let index = TideModel::find_time_index(dates, now)? as usize;
+        let (current, next) = (dates.get(index)?, dates.get(index + 1)?);
+
+        //Ties go to the earlier sample
+        let halfway = *current + next.signed_duration_since(*current) / 2;
+        Some(halfway + Duration::seconds(1))
+    }
+
+    // Next time the sample nearest to now changes, which moves the water mark and play head
+    pub fn next_sample_change(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        TideModel::next_index_change(&self.dates, now)
+    }
+
+    // Average time between samples. The dates are rounded to the minute, so neighbours can be
+    // a little further apart or closer together. None with less than two
+    pub fn sample_interval(&self) -> Option<Duration> {
+        let (first, last) = self.get_date_range()?;
+        let gaps = self.dates.len() as i32 - 1;
+
+        match gaps {
+            0 => None,
+            _ => Some(last.signed_duration_since(*first) / gaps),
+        }
+    }
+
+    pub fn get_date_range(&self) -> Option<(&DateTime<Utc>, &DateTime<Utc>)> {
+        if let Some(first) = self.dates.first() {
+            if let Some(last) = self.dates.last() {
+                return Some((first, last));
+            }
+        }
+
+        None
+    }
+
+    pub fn get_current_norm_height(&self, now: DateTime<Utc>) -> f32 {
+        if let Some(index) = TideModel::find_time_index(&self.dates, now) {
+            if let Some(height) = self.normalised_heights.get(index as usize) {
+                return height.to_owned();
+            }
+        }
+
+        // Essentially marker will be at zero
+        -10_f32
+    }
+
+    pub fn upcoming_extremes(&self, now: DateTime<Utc>) -> &[TideExtremeGraphData] {
+        let start = self
+            .extremes
+            .iter()
+            .position(|e| e.date >= now)
+            .unwrap_or(self.extremes.len());
+
+        &self.extremes[start..]
+    }
+
+    //Summarise the heights between two points in time, None if there is no data in that range
+    pub fn statistics(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Option<TideStatistics> {
+        let heights: Vec<f32> = self
+            .dates
+            .iter()
+            .zip(self.heights.iter())
+            .filter(|(date, _)| **date >= from && **date < to)
+            .map(|(_, height)| height.to_owned())
+            .collect();
+
+        if heights.is_empty() {
+            return None;
+        }
+
+        let high_water = heights.iter().cloned().fold(f32::MIN, f32::max);
+        let low_water = heights.iter().cloned().fold(f32::MAX, f32::min);
+        let mean_water = heights.iter().sum::<f32>() / heights.len() as f32;
+
+        Some(TideStatistics {
+            high_water,
+            low_water,
+            mean_water,
+            range: high_water - low_water,
+        })
+    }
+}
+
+impl TideModelWindow<'_> {
+    pub fn extremes(&self) -> &[TideExtremeGraphData] {
+        for (index, extreme) in self.extremes.iter().enumerate() {
+            if extreme.index >= self.start_index {
+                return &self.extremes[index..];
+            }
+        }
+
+        //Return empty slice
+        &self.extremes[0..0]
+    }
+
+    //Tide extremes are recorded with an offset relative to the original window
+    pub fn get_extreme_index_in_window(&self, extreme_index: u32) -> u32 {
+        extreme_index - self.start_index
+    }
+
+    pub fn water_mark(&self) -> &WaterMarkData {
+        &self.water_mark
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct WaterMarkData {
+    pub high_water: f32,
+    pub low_water: f32,
+    pub current_water: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TideResponse {
+    pub station: String,
+    pub heights: Vec<TideHeightData>,
+    pub extremes: Vec<TideExtremesData>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TideHeightData {
+    dt: u32,
+    #[serde(with = "my_date_format")]
+    date: DateTime<Utc>,
+    pub height: f32,
+}
+
+// impl TideHeightData {
+//     pub fn nil() -> TideHeightData {
+//         TideHeightData {
+//             height: 0_f32,
+//             date: "".to_string(),
+//             dt: 0_u32,
+//         }
+//     }
+// }
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TideStatistics {
+    pub high_water: f32,
+    pub low_water: f32,
+    pub mean_water: f32,
+    pub range: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExtremeKind {
+    High,
+    Low,
+}
+
+impl ExtremeKind {
+    fn from_api(extreme_type: &str) -> ExtremeKind {
+        match extreme_type {
+            "High" => ExtremeKind::High,
+            _ => ExtremeKind::Low,
+        }
+    }
+}
+
+pub struct TideExtremeGraphData {
+    index: u32,
+    date: DateTime<Utc>,
+    height: f32,
+    kind: ExtremeKind,
+}
+
+impl TideExtremeGraphData {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn date(&self) -> DateTime<Utc> {
+        self.date
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    pub fn kind(&self) -> ExtremeKind {
+        self.kind
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TideExtremesData {
+    dt: u32,
+    #[serde(with = "my_date_format")]
+    date: DateTime<Utc>,
+    height: f32,
+    #[serde(rename = "type")]
+    extreme_type: String,
+}
+
+impl TideResponse {
+    pub fn nil() -> TideResponse {
+        TideResponse {
+            station: String::new(),
+            heights: vec![],
+            extremes: vec![],
+        }
+    }
+}
+
+//https://serde.rs/custom-date-format.html
+mod my_date_format {
+    use alloc::{format, string::String};
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y-%m-%dT%H:%M%z"; //2020-09-08T10:00+0000
+
+    // The signature of a serialize_with function must follow the pattern:
+    //
+    //    fn serialize<S>(&T, S) -> Result<S::Ok, S::Error>
+    //    where
+    //        S: Serializer
+    //
+    // although it may also be generic over the input types T.
+    pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let s = format!("{}", date.format(FORMAT));
+        serializer.serialize_str(&s)
+    }
+
+    // The signature of a deserialize_with function must follow the pattern:
+    //
+    //    fn deserialize<'de, D>(D) -> Result<T, D::Error>
+    //    where
+    //        D: Deserializer<'de>
+    //
+    // although it may also be generic over the output types T.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Utc.datetime_from_str(&s, FORMAT)
+            .map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,514 @@
+// The clock itself: loads the settings, picks the render device and runs the main loop.
+// main.rs only hands it the command line
+use crate::burn_in::BurnInSettings;
+use crate::display::{self, Backend, RenderDevice, Transition};
+use crate::font;
+use crate::layout::{self, FrameContext, Layout, Rotation};
+use crate::mirror::MirrorDevice;
+use crate::mono_frame::MonoFrame;
+use crate::night::{self, NightMode};
+use crate::paths::Paths;
+use crate::schedule;
+use crate::settings::{DisplaySettings, Settings};
+use crate::ticker;
+use crate::tides::{self, TideModel};
+use chrono::{Local, Utc};
+use std::{error::Error, thread, time};
+
+// Render devices are picked at runtime from the ones built in with cargo features,
+// see init_render_device
+#[cfg(feature = "image-writer")]
+use crate::display::ImageWriter;
+#[cfg(feature = "framebuffer")]
+use crate::framebuffer::Framebuffer;
+#[cfg(any(feature = "ssd1305", feature = "ssd1306", feature = "sh1106"))]
+use crate::oled;
+#[cfg(feature = "preview-server")]
+use crate::preview::PreviewServer;
+#[cfg(feature = "sh1106")]
+use crate::sh1106;
+#[cfg(feature = "ssd1305")]
+use crate::ssd1305;
+#[cfg(feature = "ssd1306")]
+use crate::ssd1306;
+#[cfg(feature = "terminal")]
+use crate::terminal::TerminalWriter;
+
+const MAX_RETRIES: i32 = 3;
+
+//Scrolled past at start up. It fits on a 128 wide screen, so the panels scroll it themselves
+pub const SPLASH: &str = "YOU LOOK MAGNIFICENT TODAY";
+
+// `backend` overrides the one set in Settings.toml
+pub fn run(paths: &Paths, backend: Option<Backend>) -> Result<(), Box<dyn Error>> {
+    println!("Hello, world!");
+    println!(
+        "Config {:?}, data in {:?}, assets in {:?}",
+        paths.config, paths.data_dir, paths.assets_dir
+    );
+
+    let tide_data = tides::load_tides_from_disk(paths);
+    let mut tide_model = TideModel::new(tide_data);
+
+    let range = tide_model.get_date_range().unwrap();
+    println!("Found date range on disk: {:?} at {:?}", range, Utc::now());
+
+    let settings = Settings::load(paths.settings())?;
+    let backend = backend.or(settings.display.backend).unwrap_or_default();
+
+    let font = font::load(paths);
+
+    let layout = load_layout(paths);
+
+    //Because we're using RenderDevice to hold our reference (aka Trait Object), we don't know the concrete type. This means
+    //we need to use a box pointer
+    let mut render_device: Box<dyn RenderDevice> =
+        init_render_device(backend, &settings.display, paths)?;
+    println!("Rendering to {}", backend.name());
+    if !settings.display.mirror.is_empty() {
+        render_device = init_mirrors(backend, render_device, &settings.display, paths)?;
+    }
+    apply_display_settings(render_device.as_mut(), &settings.display)?;
+
+    splash(
+        render_device.as_mut(),
+        &font,
+        time::Duration::from_secs(4),
+        time::Duration::from_secs(5),
+    );
+
+    //let p = Path::new("resources/FaceDisp.r6.png");
+    //let mut img = image::open(p).unwrap().to_rgb();
+
+    //let mut offset = 0;
+    let mut retries = 0;
+    let mut last_frame: Option<(usize, MonoFrame)> = None;
+    let mut night_mode: Option<NightMode> = None;
+    //Set when the device should get the frame even if it hasn't changed
+    let mut redraw = true;
+
+    loop {
+        // Test time logic
+        // offset += 1;
+        // let duration = Duration::minutes(offset * 16);
+        // let now = Local::now().checked_add_signed(duration).unwrap();
+        let now = Local::now();
+
+        let mode = settings.night.as_ref().and_then(|n| n.mode_at(now));
+        if mode != night_mode {
+            if let Some(night) = &settings.night {
+                println!("Night mode {:?}", mode);
+                match night::apply(render_device.as_mut(), mode, night, &settings.display) {
+                    Ok(()) => {
+                        night_mode = mode;
+                        //Emulated controls only show up on the next render
+                        redraw = true;
+                    }
+                    Err(e) => println!("Could not switch night mode, retrying. Err {}", e),
+                }
+            }
+        }
+
+        //Nothing to draw with the panel off, the tide data can catch up in the morning
+        if night_mode == Some(NightMode::Sleep) {
+            let morning = settings.night.as_ref().and_then(|n| n.next_change(now));
+            thread::sleep(schedule::sleep_until(Utc::now(), vec![morning]));
+            continue;
+        }
+
+        //Enough for every graph, including the ones showing tomorrow
+        let samples_needed =
+            layout.samples_needed(render_device.size(), tide_model.sample_interval());
+        let (window, is_data_fresh) = tide_model.get_window(now, samples_needed);
+
+        let window = match is_data_fresh {
+            tides::DataFreshness::Fresh => {
+                retries = 0;
+                window
+            }
+            tides::DataFreshness::NeedsUpdate => {
+                println!("Data needs update, loading api");
+                retries += 1;
+
+                if retries > MAX_RETRIES {
+                    panic!(
+                        "Could not refresh tide data after 3 attempts. Aborting and shutting down"
+                    );
+                }
+
+                //Blocking - not quite sure yet what the best paradigm is for async code
+                let response = tides::load_tides_from_api(&settings, paths)?;
+
+                tide_model = TideModel::new(response);
+
+                //Print confirmation to log
+                let range = tide_model.get_date_range().unwrap();
+                println!(
+                    "Loaded date range: {:?} at {:?}",
+                    range,
+                    tides::local_to_utc(now)
+                );
+
+                let (window, _is_data_fresh) = tide_model.get_window(now, samples_needed);
+                window
+            }
+        };
+
+        let page_index = layout.current_page_index(night_mode, now);
+
+        let ctx = FrameContext {
+            screen: render_device.size(),
+            font: &font,
+            tide_model: &tide_model,
+            tide_window: &window,
+            local_time: now,
+        };
+
+        let painted = paint(
+            &mut render_device,
+            &layout,
+            page_index,
+            &ctx,
+            &settings.burn_in,
+            &mut last_frame,
+            redraw,
+        );
+
+        //A glitch on the bus shouldn't stop the clock. Reset the display and try again shortly
+        if let Err(e) = painted {
+            println!("Could not render, reinitialising the display. Err {}", e);
+            if let Err(e) = render_device.reinit() {
+                println!("Could not reinitialise the display. Err {}", e);
+            }
+
+            //Whatever was on screen is gone, redraw the page without a transition
+            last_frame = None;
+            thread::sleep(time::Duration::from_millis(1000));
+            continue;
+        }
+        redraw = false;
+
+        //Mirrors send frames they held back earlier once they're due
+        if let Err(e) = render_device.flush() {
+            println!("Could not flush the display. Err {}", e);
+        }
+
+        //Nothing on screen changes until the next of these
+        let page_change = match night_mode {
+            Some(NightMode::Minimal) => None,
+            _ => layout.next_page_change(now),
+        };
+        let changes = vec![
+            layout.page(page_index).next_change(&ctx),
+            page_change,
+            settings.burn_in.next_change(now),
+            settings.night.as_ref().and_then(|n| n.next_change(now)),
+            render_device
+                .next_flush()
+                .and_then(|wait| chrono::Duration::from_std(wait).ok())
+                .map(|wait| Utc::now() + wait),
+        ];
+
+        thread::sleep(schedule::sleep_until(Utc::now(), changes))
+    }
+}
+
+fn paint(
+    render_device: &mut Box<dyn RenderDevice>,
+    layout: &Layout,
+    page_index: usize,
+    ctx: &FrameContext,
+    burn_in: &BurnInSettings,
+    last_frame: &mut Option<(usize, MonoFrame)>,
+    redraw: bool,
+) -> Result<(), Box<dyn Error>> {
+    let img = layout::compose(layout.page(page_index), ctx);
+    let img = burn_in.apply(&img, ctx.local_time);
+
+    if let Some((last_index, last_img)) = last_frame {
+        if *last_index != page_index {
+            play_transition(render_device, &layout.rotation, last_img, &img)?;
+        } else if *last_img == img && !redraw {
+            //Same as what's on screen, spare the bus
+            return Ok(());
+        }
+    }
+
+    render_device.render(&img)?;
+    *last_frame = Some((page_index, img));
+
+    Ok(())
+}
+
+//Blocking, plays the in between frames when the page changes
+fn play_transition(
+    render_device: &mut Box<dyn RenderDevice>,
+    rotation: &Rotation,
+    from: &MonoFrame,
+    to: &MonoFrame,
+) -> Result<(), Box<dyn Error>> {
+    if rotation.transition == Transition::None {
+        return Ok(());
+    }
+
+    for frame in 1..rotation.transition_frames {
+        let progress = frame as f32 / rotation.transition_frames as f32;
+        let img = display::transition_frame(from, to, rotation.transition, progress);
+
+        render_device.render(&img)?;
+        thread::sleep(time::Duration::from_millis(rotation.transition_frame_ms));
+    }
+
+    Ok(())
+}
+
+// Centres a line of text, for the splash
+// The greeting for `greeting`, then SPLASH scrolling past for `ticker`. It's only for show,
+// so like the main loop a glitch on the bus resets the display rather than stopping the clock
+fn splash(
+    render_device: &mut dyn RenderDevice,
+    font: &font::Font5,
+    greeting: time::Duration,
+    ticker: time::Duration,
+) {
+    let (width, height) = render_device.size();
+    let mut backbuffer: MonoFrame = MonoFrame::new(width, height);
+
+    render("HELLO TIM!", font, &mut backbuffer);
+    if let Err(e) = render_device.render(&backbuffer) {
+        reinit_after(render_device, e);
+    }
+    thread::sleep(greeting);
+
+    if let Err(e) = ticker::play(render_device, font, SPLASH, ticker) {
+        reinit_after(render_device, e);
+    }
+}
+
+fn reinit_after(render_device: &mut dyn RenderDevice, e: Box<dyn Error>) {
+    println!("Could not render, reinitialising the display. Err {}", e);
+    if let Err(e) = render_device.reinit() {
+        println!("Could not reinitialise the display. Err {}", e);
+    }
+}
+
+pub fn render(text: &str, font: &font::Font5, backbuffer: &mut MonoFrame) {
+    println!("{}", text);
+
+    let mut width = 0;
+    for c in text.chars() {
+        if let Some(si) = font.faces.get(&c) {
+            width += si.width() + 1;
+        }
+    }
+
+    let mut caret = (backbuffer.width() / 2).saturating_sub(width / 2);
+    let top = (backbuffer.height() / 2).saturating_sub(3);
+    for c in text.chars() {
+        //println!("{}", c);
+
+        if let Some(si) = font.faces.get(&c) {
+            backbuffer.blit(si, caret, top);
+            caret += si.width() + 1;
+        }
+    }
+}
+
+// A broken Layout.toml shouldn't leave the clock blank
+pub fn load_layout(paths: &Paths) -> Layout {
+    Layout::load(paths.layout()).unwrap_or_else(|e| {
+        println!("Could not load layout, using the default face. Err {}", e);
+        Layout::default()
+    })
+}
+
+fn apply_display_settings(
+    render_device: &mut dyn RenderDevice,
+    display: &DisplaySettings,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(controls) = render_device.controls() {
+        if let Some(contrast) = display.contrast {
+            controls.set_contrast(contrast)?;
+        }
+        controls.set_rotated(display.rotate_180)?;
+        controls.set_inverted(display.invert)?;
+    }
+
+    Ok(())
+}
+
+//Without any backend features there is nothing to size
+#[cfg_attr(
+    not(any(
+        feature = "ssd1305",
+        feature = "ssd1306",
+        feature = "sh1106",
+        feature = "image-writer",
+        feature = "terminal",
+        feature = "preview-server",
+        feature = "framebuffer"
+    )),
+    allow(unused_variables)
+)]
+fn init_render_device(
+    backend: Backend,
+    display: &DisplaySettings,
+    paths: &Paths,
+) -> Result<Box<dyn RenderDevice>, Box<dyn Error>> {
+    match backend {
+        #[cfg(feature = "ssd1305")]
+        Backend::Ssd1305 => init_panel(ssd1305::Ssd1305, display),
+        #[cfg(feature = "ssd1306")]
+        Backend::Ssd1306 => init_panel(ssd1306::Ssd1306, display),
+        #[cfg(feature = "sh1106")]
+        Backend::Sh1106 => init_panel(sh1106::Sh1106, display),
+        #[cfg(feature = "image-writer")]
+        Backend::ImageWriter => Ok(Box::new(ImageWriter::with_path(
+            display.width,
+            display.height,
+            paths.display_image(),
+        ))),
+        #[cfg(feature = "terminal")]
+        Backend::Terminal => Ok(Box::new(TerminalWriter::new(display.width, display.height))),
+        #[cfg(feature = "preview-server")]
+        Backend::PreviewServer => {
+            let server = PreviewServer::bind(display.width, display.height, &display.preview)?;
+            println!("Preview at http://{}/", server.address());
+            Ok(Box::new(server))
+        }
+        #[cfg(feature = "framebuffer")]
+        Backend::Framebuffer => Ok(Box::new(Framebuffer::open(
+            display.width,
+            display.height,
+            &display.framebuffer,
+        )?)),
+        #[allow(unreachable_patterns)]
+        _ => Err(simple_error::SimpleError::new(format!(
+            "This build doesn't include the {0} display, rebuild with `--features {0}`",
+            backend.name()
+        ))
+        .into()),
+    }
+}
+
+// The main device plus the [[display.mirror]] ones, all getting the same frames
+fn init_mirrors(
+    backend: Backend,
+    device: Box<dyn RenderDevice>,
+    display: &DisplaySettings,
+    paths: &Paths,
+) -> Result<Box<dyn RenderDevice>, Box<dyn Error>> {
+    let mut mirror = MirrorDevice::new(backend.name(), device);
+
+    for settings in &display.mirror {
+        let device = init_render_device(settings.backend, display, paths)?;
+        println!(
+            "Mirroring to {}, every {}s",
+            settings.backend.name(),
+            settings.every_seconds
+        );
+        mirror.add(
+            settings.backend.name(),
+            device,
+            time::Duration::from_secs(settings.every_seconds),
+        );
+    }
+
+    Ok(Box::new(mirror))
+}
+
+#[cfg(any(feature = "ssd1305", feature = "ssd1306", feature = "sh1106"))]
+fn init_panel<C: oled::Chip + 'static>(
+    chip: C,
+    display: &DisplaySettings,
+) -> Result<Box<dyn RenderDevice>, Box<dyn Error>> {
+    let mut controller = oled::rpi::init(
+        chip,
+        &display.transport,
+        display.width as usize,
+        display.height as usize,
+    )?;
+    controller.clear();
+    controller.set_pixel(5, 5, 1);
+    controller.display()?;
+    Ok(Box::new(controller))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_hal::{Event, MockBus};
+    use crate::oled::{OledController, SpiTransport};
+    use crate::snapshot::with_fixture_ctx;
+    use crate::ssd1305::Ssd1305;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // Counts what reaches the device
+    struct CountingDevice {
+        frames: Rc<Cell<usize>>,
+    }
+
+    impl RenderDevice for CountingDevice {
+        fn render(&mut self, _buffer: &MonoFrame) -> Result<(), Box<dyn Error>> {
+            self.frames.set(self.frames.get() + 1);
+            Ok(())
+        }
+
+        fn size(&self) -> (u32, u32) {
+            (128, 32)
+        }
+    }
+
+    #[test]
+    fn test_unchanged_frame_is_skipped() {
+        let layout = Layout::default();
+        let burn_in = BurnInSettings::default();
+        let frames = Rc::new(Cell::new(0));
+        let mut device: Box<dyn RenderDevice> = Box::new(CountingDevice {
+            frames: frames.clone(),
+        });
+        let mut last_frame = None;
+
+        let mut paint_at = |time: &str, device: &mut Box<dyn RenderDevice>, redraw: bool| {
+            with_fixture_ctx(time, |ctx| {
+                paint(device, &layout, 0, ctx, &burn_in, &mut last_frame, redraw).unwrap();
+            });
+        };
+
+        paint_at("2020-10-02T19:00:00Z", &mut device, false);
+        //Half a second on the face is the same
+        paint_at("2020-10-02T19:00:00.500Z", &mut device, false);
+        paint_at("2020-10-02T19:00:00.500Z", &mut device, true);
+        //The colon blinks
+        paint_at("2020-10-02T19:00:01Z", &mut device, false);
+
+        assert_eq!(frames.get(), 3);
+    }
+
+    #[test]
+    fn test_splash_survives_a_bus_glitch() {
+        let font = font::init();
+        let bus = MockBus::default();
+        let transport = SpiTransport::new(bus.spi(), bus.dc());
+        let mut device = OledController::new(Ssd1305, transport, Some(bus.rst()), 128, 32);
+        device.init().unwrap();
+        bus.clear();
+
+        //The greeting doesn't make it, the panel is reset and the ticker carries on
+        bus.fail_next_write();
+        splash(
+            &mut device,
+            &font,
+            time::Duration::from_millis(0),
+            time::Duration::from_millis(10),
+        );
+
+        let events = bus.events();
+        let reset = events
+            .iter()
+            .position(|e| *e == Event::Reset(false))
+            .unwrap();
+        assert!(events[reset..].contains(&Event::Command(0x2F)));
+    }
+}
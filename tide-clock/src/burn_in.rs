@@ -0,0 +1,255 @@
+use crate::mono_frame::MonoFrame;
+use crate::schedule;
+use chrono::{DateTime, Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+// The [burn_in] section of Settings.toml. The face is static, so without this the same
+// pixels stay lit around the clock and wear out before the rest of the panel
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct BurnInSettings {
+    //Furthest the face moves from its layout position in any direction, 0 keeps it still
+    #[serde(default = "default_shift")]
+    pub shift: u32,
+    #[serde(default = "default_shift_minutes")]
+    pub shift_minutes: u32,
+    //Every so many minutes the whole face is shown inverted for invert_seconds, 0 turns it off
+    #[serde(default)]
+    pub invert_minutes: u32,
+    #[serde(default = "default_invert_seconds")]
+    pub invert_seconds: u32,
+}
+
+fn default_shift() -> u32 {
+    1
+}
+
+fn default_shift_minutes() -> u32 {
+    5
+}
+
+fn default_invert_seconds() -> u32 {
+    2
+}
+
+impl Default for BurnInSettings {
+    fn default() -> BurnInSettings {
+        BurnInSettings {
+            shift: default_shift(),
+            shift_minutes: default_shift_minutes(),
+            invert_minutes: 0,
+            invert_seconds: default_invert_seconds(),
+        }
+    }
+}
+
+impl BurnInSettings {
+    // Walks the square of offsets row by row, alternating direction, so each step moves the
+    // face by a single pixel. Like the page rotation it runs on the wall clock
+    pub fn offset_at(&self, local_time: DateTime<Local>) -> (i32, i32) {
+        if self.shift == 0 {
+            return (0, 0);
+        }
+
+        let side = 2 * self.shift as i64 + 1;
+        let minutes = local_time.timestamp().div_euclid(60);
+        let step = (minutes / self.shift_minutes.max(1) as i64).rem_euclid(side * side);
+
+        let (row, column) = (step / side, step % side);
+        let column = match row % 2 {
+            0 => column,
+            _ => side - 1 - column,
+        };
+
+        let shift = self.shift as i64;
+        ((column - shift) as i32, (row - shift) as i32)
+    }
+
+    pub fn is_inverted_at(&self, local_time: DateTime<Local>) -> bool {
+        if self.invert_minutes == 0 {
+            return false;
+        }
+
+        local_time
+            .timestamp()
+            .rem_euclid(self.invert_minutes as i64 * 60)
+            < self.invert_seconds as i64
+    }
+
+    // The next time the offset moves or the inversion starts or ends, None when both are off
+    pub fn next_change(&self, local_time: DateTime<Local>) -> Option<DateTime<Utc>> {
+        let shift = match self.shift {
+            0 => None,
+            _ => Some(schedule::next_boundary(
+                &local_time,
+                self.shift_minutes.max(1) as i64 * 60,
+            )),
+        };
+
+        let invert = match self.invert_minutes {
+            0 => None,
+            minutes => {
+                let next_start = schedule::next_boundary(&local_time, minutes as i64 * 60);
+                let end = next_start - Duration::minutes(minutes as i64)
+                    + Duration::seconds(self.invert_seconds as i64);
+                match end > local_time.with_timezone(&Utc) {
+                    true => Some(end.min(next_start)),
+                    false => Some(next_start),
+                }
+            }
+        };
+
+        shift.into_iter().chain(invert).min()
+    }
+
+    // Applied to the composed frame before it goes to the render device. The face is shifted
+    // as a whole and clipped, whatever is moved past an edge is lost until it moves back. The
+    // shipped face is lit right up to all four edges, so there's no room to move within
+    pub fn apply(&self, frame: &MonoFrame, local_time: DateTime<Local>) -> MonoFrame {
+        let (dx, dy) = self.offset_at(local_time);
+        let mut out = frame.shifted(dx, dy);
+
+        if self.is_inverted_at(local_time) {
+            out.invert();
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{self, Layout};
+    use crate::settings::Settings;
+    use crate::snapshot::{fixture_time, init_tz, with_fixture_ctx};
+    use chrono::Duration;
+    use std::collections::HashSet;
+
+    fn at(time: &str) -> DateTime<Local> {
+        init_tz();
+        fixture_time(time).with_timezone(&Local)
+    }
+
+    #[test]
+    fn test_offset_visits_every_position_one_pixel_at_a_time() {
+        let settings = BurnInSettings {
+            shift: 1,
+            shift_minutes: 5,
+            ..BurnInSettings::default()
+        };
+        let start = at("2020-10-02T00:00:00Z");
+
+        let offsets: Vec<(i32, i32)> = (0..9)
+            .map(|i| settings.offset_at(start + Duration::minutes(i * 5)))
+            .collect();
+
+        assert_eq!(offsets.iter().collect::<HashSet<_>>().len(), 9);
+        for pair in offsets.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert_eq!((a.0 - b.0).abs() + (a.1 - b.1).abs(), 1);
+        }
+        assert!(offsets.iter().all(|o| o.0.abs() <= 1 && o.1.abs() <= 1));
+
+        //Holds still between steps, and repeats once the square is done
+        assert_eq!(settings.offset_at(start + Duration::minutes(4)), offsets[0]);
+        assert_eq!(
+            settings.offset_at(start + Duration::minutes(45)),
+            offsets[0]
+        );
+    }
+
+    #[test]
+    fn test_no_shift() {
+        let settings = BurnInSettings {
+            shift: 0,
+            ..BurnInSettings::default()
+        };
+
+        assert_eq!(settings.offset_at(at("2020-10-02T03:17:00Z")), (0, 0));
+    }
+
+    #[test]
+    fn test_shift_clips_at_the_edges() {
+        let settings = BurnInSettings {
+            shift: 1,
+            ..BurnInSettings::default()
+        };
+        //Lit along the left edge
+        let frame = MonoFrame::from_fn(4, 4, |x, _| x == 0);
+        let time = (0..9)
+            .map(|i| at("2020-10-02T00:00:00Z") + Duration::minutes(i * 5))
+            .find(|t| settings.offset_at(*t) == (-1, 0))
+            .unwrap();
+
+        assert_eq!(settings.apply(&frame, time), MonoFrame::new(4, 4));
+    }
+
+    #[test]
+    fn test_shipped_face_moves() {
+        let settings = Settings::load("resources/Settings.toml").unwrap().burn_in;
+        let layout = Layout::load("resources/Layout.toml").unwrap();
+        let face = with_fixture_ctx("2020-10-02T19:00:00Z", |ctx| {
+            layout::compose(layout.page(0), ctx)
+        });
+        //Lit from edge to edge, which used to leave it nowhere to go
+        assert_eq!(face.lit_bounds(), Some((0, 0, 127, 31)));
+
+        let start = at("2020-10-02T19:00:00Z");
+        let frames: Vec<MonoFrame> = (0..9)
+            .map(|i| settings.apply(&face, start + Duration::minutes(i * 5)))
+            .collect();
+
+        for (i, a) in frames.iter().enumerate() {
+            for b in frames.iter().skip(i + 1) {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_periodic_inversion() {
+        let settings = BurnInSettings {
+            shift: 0,
+            invert_minutes: 10,
+            invert_seconds: 2,
+            ..BurnInSettings::default()
+        };
+        let frame = MonoFrame::from_fn(2, 2, |x, _| x == 0);
+
+        let inverted = settings.apply(&frame, at("2020-10-02T00:10:01Z"));
+        assert_eq!(inverted, MonoFrame::from_fn(2, 2, |x, _| x == 1));
+
+        assert_eq!(settings.apply(&frame, at("2020-10-02T00:10:02Z")), frame);
+        assert!(!BurnInSettings::default().is_inverted_at(at("2020-10-02T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_next_change() {
+        let settings = BurnInSettings {
+            invert_minutes: 10,
+            ..BurnInSettings::default()
+        };
+        let next = |time| settings.next_change(at(time)).unwrap();
+
+        //The shift moves every 5 minutes
+        assert_eq!(
+            next("2020-10-02T00:02:00Z"),
+            fixture_time("2020-10-02T00:05:00Z")
+        );
+        //The inversion ends 2 seconds in, then starts again after 10 minutes
+        assert_eq!(
+            next("2020-10-02T00:10:00Z"),
+            fixture_time("2020-10-02T00:10:02Z")
+        );
+        assert_eq!(
+            next("2020-10-02T00:10:02Z"),
+            fixture_time("2020-10-02T00:15:00Z")
+        );
+
+        let still = BurnInSettings {
+            shift: 0,
+            ..BurnInSettings::default()
+        };
+        assert_eq!(still.next_change(at("2020-10-02T00:10:00Z")), None);
+    }
+}
@@ -0,0 +1,454 @@
+// The command line. Hand rolled like the old --display flag, there are only a few options
+use crate::app;
+use crate::display::Backend;
+use crate::font;
+use crate::layout::{self, FrameContext};
+use crate::mono_frame::MonoFrame;
+use crate::paths::Paths;
+use crate::settings::{self, Secrets, Settings};
+use crate::tides::{self, ExtremeKind, TideModel, TideResponse};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use simple_error::SimpleError;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+const USAGE: &str = "\
+Usage: tide-clock [COMMAND] [OPTIONS]
+
+Commands:
+  run             Run the clock, the default
+  fetch           Download the next few days of tides into the data directory
+  render          Draw one frame to a PNG
+  table           Print the upcoming high and low tides
+  check-config    Load the settings, layout and secrets and report any problems
+  help            Print this message
+
+Options:
+  --config <file>       Settings.toml to use, Layout.toml and Secrets.toml are read from
+                        beside it [env: TIDE_CLOCK_CONFIG]
+  --data-dir <dir>      Where the tides and display.bmp are kept [env: TIDE_CLOCK_DATA_DIR]
+  --assets-dir <dir>    Where a Font-5px.png replacing the built in font is
+                        [env: TIDE_CLOCK_ASSETS_DIR]
+  --display <backend>   run: overrides the backend set in Settings.toml
+  --at <time>           render: when to draw, 2020-10-02T19:00 local or RFC 3339
+                        [default: now]
+  --out <file>          render: where to save the PNG [default: frame.png]
+
+From a checkout everything is in resources/. Otherwise the defaults are
+~/.config/tide-clock/Settings.toml, ~/.cache/tide-clock and ~/.local/share/tide-clock
+or /usr/share/tide-clock, following the XDG_* variables when they're set.
+";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run {
+        backend: Option<Backend>,
+    },
+    Fetch,
+    Render {
+        at: Option<DateTime<Utc>>,
+        out: PathBuf,
+    },
+    Table,
+    CheckConfig,
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub paths: Paths,
+}
+
+// args as from std::env::args, program name first. The options override the given paths,
+// normally from Paths::locate
+pub fn parse(args: &[String], mut paths: Paths) -> Result<Cli, Box<dyn Error>> {
+    let mut args = args.iter().skip(1).peekable();
+
+    //Straight into the options means run, like before there were commands
+    let name = match args.peek() {
+        Some(arg) if !arg.starts_with('-') => args.next().unwrap().as_str(),
+        _ => "run",
+    };
+
+    let (mut backend, mut at, mut out) = (None, None, None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => {
+                return Ok(Cli {
+                    command: Command::Help,
+                    paths,
+                })
+            }
+            "--config" => paths.config = PathBuf::from(value(&mut args, arg)?),
+            "--data-dir" => paths.data_dir = PathBuf::from(value(&mut args, arg)?),
+            "--assets-dir" => paths.assets_dir = PathBuf::from(value(&mut args, arg)?),
+            "--display" => backend = Some(value(&mut args, arg)?.parse()?),
+            "--at" => at = Some(parse_time(value(&mut args, arg)?)?),
+            "--out" => out = Some(PathBuf::from(value(&mut args, arg)?)),
+            _ => return Err(usage_error(format!("Unknown option `{}`", arg))),
+        }
+    }
+
+    //Options for a different command are a mistake, better said than ignored
+    if backend.is_some() && name != "run" {
+        return Err(usage_error("--display only applies to run".to_string()));
+    }
+    if (at.is_some() || out.is_some()) && name != "render" {
+        return Err(usage_error(
+            "--at and --out only apply to render".to_string(),
+        ));
+    }
+
+    let command = match name {
+        "run" => Command::Run { backend },
+        "fetch" => Command::Fetch,
+        "render" => Command::Render {
+            at,
+            out: out.unwrap_or_else(|| PathBuf::from("frame.png")),
+        },
+        "table" => Command::Table,
+        "check-config" => Command::CheckConfig,
+        "help" => Command::Help,
+        _ => return Err(usage_error(format!("Unknown command `{}`", name))),
+    };
+
+    Ok(Cli { command, paths })
+}
+
+fn value<'a, I>(args: &mut I, option: &str) -> Result<&'a str, Box<dyn Error>>
+where
+    I: Iterator<Item = &'a String>,
+{
+    match args.next() {
+        Some(value) => Ok(value),
+        None => Err(usage_error(format!("{} needs a value", option))),
+    }
+}
+
+fn usage_error(message: String) -> Box<dyn Error> {
+    SimpleError::new(format!("{}, see `tide-clock help`", message)).into()
+}
+
+// RFC 3339, or a local time without the offset
+fn parse_time(s: &str) -> Result<DateTime<Utc>, Box<dyn Error>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+        .map_err(|_| {
+            usage_error(format!(
+                "Can't read the time `{}`, expected e.g. 2020-10-02T19:00",
+                s
+            ))
+        })?;
+
+    match Local.from_local_datetime(&naive).earliest() {
+        Some(time) => Ok(time.with_timezone(&Utc)),
+        None => Err(SimpleError::new(format!("{} is skipped by the clocks changing", s)).into()),
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let cli = parse(args, Paths::locate())?;
+
+    match cli.command {
+        Command::Run { backend } => app::run(&cli.paths, backend),
+        Command::Fetch => fetch(&cli.paths),
+        Command::Render { at, out } => {
+            let at = at.unwrap_or_else(Utc::now);
+            render_frame(&cli.paths, at)?.to_rgb().save(&out)?;
+            println!(
+                "Saved the face at {} to {:?}",
+                at.with_timezone(&Local),
+                out
+            );
+            Ok(())
+        }
+        Command::Table => {
+            let model = TideModel::new(tides::load_tides_from_disk(&cli.paths));
+            let lines = table(&model, Utc::now());
+            if lines.is_empty() {
+                println!("No upcoming tides in {:?}, try fetch", cli.paths.tides());
+            }
+            for line in lines {
+                println!("{}", line);
+            }
+            Ok(())
+        }
+        Command::CheckConfig => check_config(&cli.paths),
+        Command::Help => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+    }
+}
+
+fn fetch(paths: &Paths) -> Result<(), Box<dyn Error>> {
+    let settings = Settings::load(paths.settings())?;
+
+    let model = TideModel::new(tides::load_tides_from_api(&settings, paths)?);
+    match model.get_date_range() {
+        Some((from, to)) => println!("Saved tides from {} to {} in {:?}", from, to, paths.tides()),
+        None => println!(
+            "The API sent no tides, the response is in {:?}",
+            paths.tides()
+        ),
+    }
+
+    Ok(())
+}
+
+// The face the clock would show at `at`, from the tides on disk
+pub fn render_frame(paths: &Paths, at: DateTime<Utc>) -> Result<MonoFrame, Box<dyn Error>> {
+    let settings = Settings::load(paths.settings())?;
+    let layout = app::load_layout(paths);
+    let tide_model = TideModel::new(tides::load_tides_from_disk(paths));
+    let font = font::load(paths);
+
+    let local_time = at.with_timezone(&Local);
+    let screen = (settings.display.width, settings.display.height);
+    let (window, _) = tide_model.get_window(local_time, layout.graph_width(screen));
+    let night_mode = settings.night.as_ref().and_then(|n| n.mode_at(local_time));
+
+    let ctx = FrameContext {
+        screen,
+        font: &font,
+        tide_model: &tide_model,
+        tide_window: &window,
+        local_time,
+    };
+    let page = layout.page(layout.current_page_index(night_mode, local_time));
+    let img = layout::compose(page, &ctx);
+
+    Ok(settings.burn_in.apply(&img, local_time))
+}
+
+// One line per upcoming high or low, in local time
+pub fn table(model: &TideModel, now: DateTime<Utc>) -> Vec<String> {
+    model
+        .upcoming_extremes(now)
+        .iter()
+        .map(|e| {
+            let kind = match e.kind() {
+                ExtremeKind::High => "HIGH",
+                ExtremeKind::Low => "LOW",
+            };
+            let date = e.date().with_timezone(&Local);
+
+            format!(
+                "{:<4}  {}  {:>5.2}m",
+                kind,
+                date.format("%a %d %b %H:%M"),
+                e.height()
+            )
+        })
+        .collect()
+}
+
+// Loads everything the clock reads at start up, reporting on each. Fails if any of them
+// would stop it
+pub fn check_config(paths: &Paths) -> Result<(), Box<dyn Error>> {
+    let mut problems = 0;
+    let mut report = |what: &str, path: &Path, result: Result<String, Box<dyn Error>>| match result
+    {
+        Ok(note) => println!("ok     {:<8} {:?} {}", what, path, note),
+        Err(e) => {
+            problems += 1;
+            println!("error  {:<8} {:?} {}", what, path, e);
+        }
+    };
+
+    let settings = Settings::load(paths.settings()).map(|s| {
+        format!(
+            "{}x{} on {}",
+            s.display.width,
+            s.display.height,
+            s.display.backend.unwrap_or_default().name()
+        )
+    });
+    report("settings", paths.settings(), settings);
+
+    let layout = layout::Layout::load(paths.layout()).map(|l| format!("{} pages", l.pages.len()));
+    report("layout", &paths.layout(), layout);
+
+    let secrets = settings::load_config::<Secrets, _>(paths.secrets()).map(|_| String::new());
+    report("secrets", &paths.secrets(), secrets);
+
+    //Only a problem when there's a sheet overriding the built in font that won't load
+    let font = match paths.font().exists() {
+        true => font::load_file(&paths.font()).map(|f| format!("{} glyphs", f.faces.len())),
+        false => Ok("not there, using the built in font".to_string()),
+    };
+    report("font", &paths.font(), font);
+
+    report("tides", &paths.tides(), check_tides(&paths.tides()));
+
+    match problems {
+        0 => Ok(()),
+        _ => {
+            Err(SimpleError::new(format!("{} problem(s) with the configuration", problems)).into())
+        }
+    }
+}
+
+//Not having fetched yet is fine, the clock fetches on start up
+fn check_tides(path: &Path) -> Result<String, Box<dyn Error>> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok("not fetched yet".to_string()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let model = TideModel::new(serde_json::from_str::<TideResponse>(&json)?);
+    match model.get_date_range() {
+        Some((from, to)) => Ok(format!("from {} to {}", from, to)),
+        None => Ok("empty".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{assert_snapshot, fixture_model, fixture_time, init_tz};
+
+    fn args(a: &[&str]) -> Vec<String> {
+        std::iter::once("tide-clock")
+            .chain(a.iter().cloned())
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    // Where the clock would look without any options, away from whatever is on this machine
+    fn located() -> Paths {
+        Paths {
+            config: PathBuf::from("/home/pi/.config/tide-clock/Settings.toml"),
+            data_dir: PathBuf::from("/home/pi/.cache/tide-clock"),
+            assets_dir: PathBuf::from("/usr/share/tide-clock"),
+        }
+    }
+
+    fn parse(a: &[&str]) -> Result<Cli, Box<dyn Error>> {
+        super::parse(&args(a), located())
+    }
+
+    // Settings and layout from resources/, tides from the test fixture
+    fn fixture_paths() -> Paths {
+        Paths {
+            data_dir: PathBuf::from("tests/fixtures"),
+            ..Paths::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let run = parse(&[]).unwrap();
+        assert_eq!(run.command, Command::Run { backend: None });
+        assert_eq!(run.paths, located());
+
+        //The old way of picking the display still works
+        assert_eq!(
+            parse(&["--display", "ssd1305"]).unwrap().command,
+            Command::Run {
+                backend: Some(Backend::Ssd1305)
+            }
+        );
+
+        let render = parse(&[
+            "render",
+            "--at",
+            "2020-10-02T19:00:00Z",
+            "--out",
+            "face.png",
+            "--data-dir",
+            "/tmp/tides",
+        ])
+        .unwrap();
+        assert_eq!(
+            render.command,
+            Command::Render {
+                at: Some(fixture_time("2020-10-02T19:00:00Z")),
+                out: PathBuf::from("face.png"),
+            }
+        );
+        assert_eq!(render.paths.tides(), Path::new("/tmp/tides/tides.json"));
+        assert_eq!(render.paths.config, located().config);
+
+        let check = parse(&[
+            "check-config",
+            "--config",
+            "/etc/tc/Settings.toml",
+            "--assets-dir",
+            "/usr/share/tc",
+        ])
+        .unwrap();
+        assert_eq!(check.command, Command::CheckConfig);
+        assert_eq!(check.paths.layout(), Path::new("/etc/tc/Layout.toml"));
+        assert_eq!(check.paths.font(), Path::new("/usr/share/tc/Font-5px.png"));
+        assert_eq!(check.paths.data_dir, located().data_dir);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(&["--display"]).is_err());
+        assert!(parse(&["--display", "vga"]).is_err());
+        assert!(parse(&["frobnicate"]).is_err());
+        assert!(parse(&["run", "--verbose"]).is_err());
+        assert!(parse(&["table", "--display", "terminal"]).is_err());
+        assert!(parse(&["run", "--out", "face.png"]).is_err());
+        assert!(parse(&["render", "--at", "teatime"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_local_time() {
+        init_tz();
+
+        assert_eq!(
+            parse_time("2020-10-02T19:00").unwrap(),
+            fixture_time("2020-10-02T19:00:00Z")
+        );
+        assert_eq!(
+            parse_time("2020-10-02T21:00:00+02:00").unwrap(),
+            fixture_time("2020-10-02T19:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        init_tz();
+        let lines = table(&fixture_model(), fixture_time("2020-10-02T14:00:00Z"));
+
+        assert_eq!(lines[0], "HIGH  Fri 02 Oct 16:24   1.83m");
+        assert!(lines[1].starts_with("LOW "));
+    }
+
+    #[test]
+    fn test_render_frame() {
+        init_tz();
+        let frame = render_frame(&fixture_paths(), fixture_time("2020-10-02T19:00:00Z")).unwrap();
+
+        assert_eq!(frame.dimensions(), (128, 32));
+        assert_snapshot("render_frame", &frame);
+    }
+
+    #[test]
+    fn test_check_config() {
+        let missing = Paths {
+            config: PathBuf::from("does/not/exist/Settings.toml"),
+            ..fixture_paths()
+        };
+        assert!(check_config(&missing).is_err());
+
+        //The clock fetches on start up, so no tides yet isn't a problem
+        assert_eq!(
+            check_tides(Path::new("does/not/exist/tides.json")).unwrap(),
+            "not fetched yet"
+        );
+        assert!(check_tides(&fixture_paths().tides())
+            .unwrap()
+            .starts_with("from "));
+    }
+}
@@ -1,368 +1,332 @@
-#[cfg(target_arch = "arm")]
-use crate::ssd1305::Ssd1305Controller;
-use crate::tides::{TideExtremeGraphData, TideModel, TideModelWindow};
-use crate::{font::Font5, maths};
-use chrono::{DateTime, Local, Utc};
+use crate::mono_frame::MonoFrame;
+use crate::oled::{Chip, OledController, Transport};
+use embedded_hal::digital::v2::OutputPin;
 use image::{Rgb, RgbImage};
-use std::{cmp::max, path::Path};
+use serde::{Deserialize, Serialize};
+use simple_error::SimpleError;
+#[cfg(feature = "image-writer")]
+use std::path::PathBuf;
+use std::{error::Error, fmt::Debug, str::FromStr, time::Duration};
+pub use tide_clock_core::paint::*;
 
-const PIXEL_WHITE: Rgb<u8> = Rgb([255_u8, 255_u8, 255_u8]);
-const PIXEL_BLACK: Rgb<u8> = Rgb([0_u8, 0_u8, 0_u8]);
-const SCREEN_WIDTH: u32 = 128;
-const SCREEN_HEIGHT: u32 = 32;
+//What panels start with when the contrast isn't configured
+pub const DEFAULT_CONTRAST: u8 = 0x80;
 
-// 0 | 1 | 0
-// 1 | 1 | 1
-// 1 | 1 | 1
-const FLOOD_FILL_MASK: [u32; 9] = [0, 1, 0, 1, 1, 1, 1, 1, 1];
+pub trait RenderDevice {
+    fn render(&mut self, buffer: &MonoFrame) -> Result<(), Box<dyn Error>>;
 
-pub struct Position {
-    x: u32,
-    y: u32,
-}
+    //Resolution in pixels, frames passed to render are expected to match
+    fn size(&self) -> (u32, u32);
 
-pub struct Bounds {
-    w: u32,
-    h: u32,
-}
+    //Called after render failed, to get the device going again. Nothing to do for most devices
+    fn reinit(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
 
-pub trait RenderDevice {
-    fn render(&mut self, buffer: &RgbImage);
-}
+    //Contrast, inversion and so on, for devices that support them
+    fn controls(&mut self) -> Option<&mut dyn DisplayControl> {
+        None
+    }
 
-#[cfg(target_arch = "arm")]
-impl RenderDevice for Ssd1305Controller {
-    fn render(&mut self, buffer: &RgbImage) {
-        self.clear();
-        //println!("clearing image");
-        for (x, y, pixel) in buffer.enumerate_pixels() {
-            let x = x as usize;
-            let y = y as usize;
-            let c: u8 = pixel[0]; //Access red channel
-            self.set_pixel(x, y, c);
-        }
-        self.display();
+    //For devices that can scroll the picture themselves, others get new frames instead
+    fn scroller(&mut self) -> Option<&mut dyn HardwareScroll> {
+        None
     }
-}
 
-pub struct ImageWriter {}
+    //For devices that hold frames back, like a throttled mirror, to send them once they're
+    //due. Called whenever the clock wakes, whether or not it had a new frame to render
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
 
-impl RenderDevice for ImageWriter {
-    fn render(&mut self, buffer: &RgbImage) {
-        let out = Path::new("resources/display.bmp");
-        buffer.save(out).unwrap();
+    //How long until a held back frame is due to be flushed, None when nothing is waiting
+    fn next_flush(&self) -> Option<Duration> {
+        None
     }
 }
 
-pub trait Painter {
-    fn paint(&self, buffer: &mut RgbImage, now: DateTime<Utc>);
+// Settings a panel can change after init. Hardware applies them itself, software backends
+// emulate them with EmulatedControls
+pub trait DisplayControl {
+    fn set_contrast(&mut self, contrast: u8) -> Result<(), Box<dyn Error>>;
+    fn set_inverted(&mut self, inverted: bool) -> Result<(), Box<dyn Error>>;
+    fn set_display_on(&mut self, on: bool) -> Result<(), Box<dyn Error>>;
+    fn set_rotated(&mut self, rotated: bool) -> Result<(), Box<dyn Error>>;
 }
 
-pub struct TextField<'a> {
-    text: String,
-    pos: Position,
-    bounds: Bounds,
-    font: &'a Font5,
+// Panels that move the last rendered frame right to left by themselves, wrapping around
+pub trait HardwareScroll {
+    fn start_scroll(&mut self) -> Result<(), Box<dyn Error>>;
+    //Back to the frame as it was rendered
+    fn stop_scroll(&mut self) -> Result<(), Box<dyn Error>>;
 }
 
-impl TextField<'_> {
-    pub fn new(text: String, font: &Font5, x: u32, y: u32) -> TextField {
-        let mut tf = TextField {
-            text: String::new(),
-            font,
-            pos: Position { x, y },
-            bounds: Bounds { w: 0, h: 0 },
-        };
-
-        tf.set_text(text); //Update bounds
+// The panel settings for backends that draw the frame themselves, applied to each frame
+// before it is output
+#[derive(Debug, PartialEq, Clone)]
+pub struct EmulatedControls {
+    pub contrast: u8,
+    pub inverted: bool,
+    pub on: bool,
+    pub rotated: bool,
+}
 
-        tf
+impl Default for EmulatedControls {
+    fn default() -> EmulatedControls {
+        EmulatedControls {
+            contrast: DEFAULT_CONTRAST,
+            inverted: false,
+            on: true,
+            rotated: false,
+        }
     }
+}
 
-    pub fn set_text(&mut self, text: String) {
-        self.text = text;
-
-        let mut width = 0;
-        let mut height: u32 = 0;
-        for c in self.text.chars() {
-            if let Some(si) = self.font.faces.get(&c) {
-                width += si.width() + 1;
-                height = max(height, si.height());
-            }
+impl EmulatedControls {
+    // The frame as the panel would show it
+    pub fn apply(&self, frame: &MonoFrame) -> MonoFrame {
+        let (width, height) = frame.dimensions();
+        if !self.on {
+            return MonoFrame::new(width, height);
         }
 
-        self.bounds = Bounds {
-            w: width,
-            h: height,
+        let mut out = match self.rotated {
+            true => MonoFrame::from_fn(width, height, |x, y| {
+                frame.get_pixel(width - 1 - x, height - 1 - y)
+            }),
+            false => frame.clone(),
         };
+        if self.inverted {
+            out.invert();
+        }
+
+        out
     }
-}
 
-impl Painter for TextField<'_> {
-    fn paint(&self, buffer: &mut RgbImage, _now: DateTime<Utc>) {
-        //bounds check
+    // How bright lit pixels are, 0 to 255. Dimmed when the contrast is turned down from the
+    // default, but even at 0 a real panel is still readable, so that maps to a dark grey
+    pub fn level(&self) -> u8 {
+        (64 + self.contrast.min(DEFAULT_CONTRAST) as u32 * 191 / DEFAULT_CONTRAST as u32) as u8
+    }
 
-        let mut caret = 0;
-        for c in self.text.chars() {
-            //println!("{}", c);
+    // Like MonoFrame::to_rgb, with lit pixels at the contrast level
+    pub fn to_rgb(&self, frame: &MonoFrame) -> RgbImage {
+        let level = self.level();
+        let lit = Rgb([level, level, level]);
+        let frame = self.apply(frame);
 
-            if let Some(si) = self.font.faces.get(&c) {
-                image::imageops::overlay(buffer, si, self.pos.x + caret, self.pos.y);
-                caret += si.width() + 1;
+        RgbImage::from_fn(frame.width(), frame.height(), |x, y| {
+            match frame.get_pixel(x, y) {
+                true => lit,
+                false => Rgb([0, 0, 0]),
             }
-        }
+        })
     }
 }
 
-pub struct GraphCanvas<'a> {
-    pos: Position,
-    bounds: Bounds,
-    data: &'a TideModelWindow<'a>,
-    font: &'a Font5,
-}
-
-impl GraphCanvas<'_> {
-    pub fn new<'a>(
-        x: u32,
-        y: u32,
-        w: u32,
-        h: u32,
-        data: &'a TideModelWindow<'a>,
-        font: &'a Font5,
-    ) -> GraphCanvas<'a> {
-        GraphCanvas {
-            pos: Position { x, y },
-            bounds: Bounds { w, h },
-            data,
-            font,
-        }
+impl DisplayControl for EmulatedControls {
+    fn set_contrast(&mut self, contrast: u8) -> Result<(), Box<dyn Error>> {
+        self.contrast = contrast;
+        Ok(())
     }
-}
 
-impl Painter for GraphCanvas<'_> {
-    fn paint(&self, buffer: &mut RgbImage, now: DateTime<Utc>) {
-        for col in 0..self.bounds.w {
-            for row in 0..self.bounds.h {
-                let raw = calculate_pixel(
-                    &self.data.normalised_heights,
-                    &self.bounds,
-                    col as i32,
-                    row as i32,
-                );
-
-                let pixel = match raw {
-                    0 => PIXEL_BLACK,
-                    1 => PIXEL_WHITE,
-                    _ => PIXEL_BLACK,
-                };
-
-                buffer.put_pixel(self.pos.x + col, self.pos.y + row, pixel);
-            }
-        }
-
-        //Race condition: labels depend on drawn wave data to draw descenders
-        for data_point in self.data.extremes().iter() {
-            let data_index_in_window = self.data.get_extreme_index_in_window(data_point.index());
-            let label = ExtremeLabel::new(self.font, data_point, data_index_in_window, &self.pos);
-            label.paint(buffer, now);
-        }
+    fn set_inverted(&mut self, inverted: bool) -> Result<(), Box<dyn Error>> {
+        self.inverted = inverted;
+        Ok(())
+    }
 
-        // Draw play head
-        let mut current_index: u32 = 0;
-        //let now = Utc.ymd(2020, 9, 14).and_hms(9, 39, 00);
-        if let Some(index) = TideModel::find_time_index(self.data.dates, now) {
-            let x = self.pos.x + index;
-            current_index = index; //record result
-
-            //Draw play head
-            if x < SCREEN_WIDTH {
-                for y in self.pos.y..SCREEN_HEIGHT {
-                    let px = match y % 2 {
-                        0 => PIXEL_WHITE,
-                        1 => PIXEL_BLACK,
-                        _ => PIXEL_BLACK,
-                    };
-
-                    buffer.put_pixel(self.pos.x + index, y, px);
-                }
-            }
-        }
+    fn set_display_on(&mut self, on: bool) -> Result<(), Box<dyn Error>> {
+        self.on = on;
+        Ok(())
+    }
 
-        // Flood fill erase to remove waves in the past
-        for col in 0..current_index {
-            for row in 0..self.bounds.h {
-                let x = col as i32;
-                let y = row as i32; //Invert y axis
-
-                let kernel: [u32; 9] = [
-                    calculate_pixel(&self.data.normalised_heights, &self.bounds, x - 1, y - 1),
-                    calculate_pixel(&self.data.normalised_heights, &self.bounds, x, y - 1),
-                    calculate_pixel(&self.data.normalised_heights, &self.bounds, x + 1, y - 1),
-                    calculate_pixel(&self.data.normalised_heights, &self.bounds, x - 1, y),
-                    calculate_pixel(&self.data.normalised_heights, &self.bounds, x, y),
-                    calculate_pixel(&self.data.normalised_heights, &self.bounds, x + 1, y),
-                    calculate_pixel(&self.data.normalised_heights, &self.bounds, x - 1, y + 1),
-                    calculate_pixel(&self.data.normalised_heights, &self.bounds, x, y + 1),
-                    calculate_pixel(&self.data.normalised_heights, &self.bounds, x + 1, y + 1),
-                ];
-
-                if should_erase(kernel, &FLOOD_FILL_MASK) {
-                    let screen_x = self.pos.x + col;
-                    let screen_y = self.pos.y + row;
-
-                    if screen_x >= SCREEN_WIDTH || screen_y >= SCREEN_HEIGHT {
-                        continue;
-                    }
-
-                    buffer.put_pixel(screen_x, screen_y, PIXEL_BLACK);
-                }
-            }
-        }
+    fn set_rotated(&mut self, rotated: bool) -> Result<(), Box<dyn Error>> {
+        self.rotated = rotated;
+        Ok(())
     }
 }
 
-fn calculate_pixel(normalized_heights: &[f32], bounds: &Bounds, x: i32, y: i32) -> u32 {
-    if x < 0 || x >= normalized_heights.len() as i32 {
-        return 1;
+impl<C, T, RST> RenderDevice for OledController<C, T, RST>
+where
+    C: Chip,
+    T: Transport,
+    RST: OutputPin,
+    RST::Error: Debug,
+{
+    fn render(&mut self, buffer: &MonoFrame) -> Result<(), Box<dyn Error>> {
+        self.load_frame(buffer);
+        self.display()?;
+        Ok(())
     }
 
-    let height = match normalized_heights.get(x as usize) {
-        Some(h) => h.to_owned(),
-        None => 0_f32,
-    };
+    fn size(&self) -> (u32, u32) {
+        (self.width() as u32, self.height() as u32)
+    }
 
-    let px_height = maths::lerp(height, 0, bounds.h as i32) as u32;
-    let y_pos = bounds.h - px_height;
+    fn reinit(&mut self) -> Result<(), Box<dyn Error>> {
+        self.init()?;
+        Ok(())
+    }
 
-    if y >= y_pos as i32 {
-        return 1;
+    fn controls(&mut self) -> Option<&mut dyn DisplayControl> {
+        Some(self)
     }
 
-    0
+    fn scroller(&mut self) -> Option<&mut dyn HardwareScroll> {
+        match self.can_scroll() {
+            true => Some(self),
+            false => None,
+        }
+    }
 }
 
-fn should_erase(kernel: [u32; 9], mask: &[u32; 9]) -> bool {
-    // kernel        mask         result (fails)
-    // 0 | 0 | 0     0 | 1 | 0    0 | 0 | 0
-    // 1 | 1 | 1  ?  1 | 1 | 1  = 1 | 1 | 1
-    // 1 | 1 | 1     1 | 1 | 1    1 | 1 | 1
-
-    for i in 0..9 {
-        let result = kernel[i] & mask[i];
+impl<C, T, RST> HardwareScroll for OledController<C, T, RST>
+where
+    C: Chip,
+    T: Transport,
+    RST: OutputPin,
+    RST::Error: Debug,
+{
+    fn start_scroll(&mut self) -> Result<(), Box<dyn Error>> {
+        OledController::start_scroll(self)?;
+        Ok(())
+    }
 
-        if result ^ mask[i] == 1 {
-            return false;
-        }
+    fn stop_scroll(&mut self) -> Result<(), Box<dyn Error>> {
+        OledController::stop_scroll(self)?;
+        Ok(())
     }
-    true
 }
 
-pub struct ExtremeLabel<'a> {
-    text_field: TextField<'a>,
-}
+impl<C, T, RST> DisplayControl for OledController<C, T, RST>
+where
+    C: Chip,
+    T: Transport,
+    RST: OutputPin,
+    RST::Error: Debug,
+{
+    fn set_contrast(&mut self, contrast: u8) -> Result<(), Box<dyn Error>> {
+        OledController::set_contrast(self, contrast)?;
+        Ok(())
+    }
 
-impl ExtremeLabel<'_> {
-    pub fn new<'a>(
-        font: &'a Font5,
-        data: &'a TideExtremeGraphData,
-        data_index: u32,
-        canvas_pos: &Position,
-    ) -> ExtremeLabel<'a> {
-        let pos = Position {
-            x: canvas_pos.x + data_index,
-            y: 0,
-        };
+    fn set_inverted(&mut self, inverted: bool) -> Result<(), Box<dyn Error>> {
+        OledController::set_inverted(self, inverted)?;
+        Ok(())
+    }
 
-        let local_tz = Local::now().timezone();
-        let local_dt = data.date().with_timezone(&local_tz);
+    fn set_display_on(&mut self, on: bool) -> Result<(), Box<dyn Error>> {
+        OledController::set_display_on(self, on)?;
+        Ok(())
+    }
 
-        ExtremeLabel {
-            text_field: TextField::new(local_dt.format("%H:%M").to_string(), font, pos.x, pos.y),
-        }
+    fn set_rotated(&mut self, rotated: bool) -> Result<(), Box<dyn Error>> {
+        OledController::set_rotated(self, rotated)?;
+        Ok(())
     }
 }
 
-impl Painter for ExtremeLabel<'_> {
-    fn paint(&self, buffer: &mut RgbImage, now: DateTime<Utc>) {
-        self.text_field.paint(buffer, now);
-
-        let baseline = self.text_field.pos.y + self.text_field.bounds.h + 2_u32;
-
-        //Draw underline
-        for i in 0..(self.text_field.bounds.w - 1) {
-            let x = self.text_field.pos.x + i;
-            let y = baseline;
+// Which RenderDevice to draw to. Named after the cargo feature that builds it in
+#[derive(Deserialize, Serialize, Debug, PartialEq, Copy, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    //Panels wired to the Pi, by controller chip. The Waveshare HAT is an SSD1305
+    Ssd1305,
+    Ssd1306,
+    Sh1106,
+    //Saves every frame to display.bmp in the data directory
+    ImageWriter,
+    //Draws every frame in the terminal
+    Terminal,
+    //Serves the last frame to browsers, see [display.preview]
+    PreviewServer,
+    //Linux framebuffer such as an SPI TFT on /dev/fb1, see [display.framebuffer]
+    Framebuffer,
+}
 
-            if x < SCREEN_WIDTH && y < SCREEN_HEIGHT {
-                buffer.put_pixel(x, y, PIXEL_WHITE);
-            }
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Ssd1305 => "ssd1305",
+            Backend::Ssd1306 => "ssd1306",
+            Backend::Sh1106 => "sh1106",
+            Backend::ImageWriter => "image-writer",
+            Backend::Terminal => "terminal",
+            Backend::PreviewServer => "preview-server",
+            Backend::Framebuffer => "framebuffer",
         }
+    }
+}
 
-        // Draw descenders
-        let x = self.text_field.pos.x;
-        let mut highest: u32 = 0;
-        if x < SCREEN_WIDTH {
-            //Find highest wave pixel
-            for y in (0..SCREEN_HEIGHT).rev() {
-                let px = buffer.get_pixel(x, y);
-
-                //When we find pixel whose r channel is 0, bail and set as highest.
-                if px[0] == 0 {
-                    highest = y;
-                    break;
-                }
-            }
+impl Default for Backend {
+    // A panel when one is built in, otherwise fall back to emulation
+    fn default() -> Backend {
+        if cfg!(feature = "ssd1305") {
+            Backend::Ssd1305
+        } else if cfg!(feature = "ssd1306") {
+            Backend::Ssd1306
+        } else if cfg!(feature = "sh1106") {
+            Backend::Sh1106
+        } else {
+            Backend::ImageWriter
+        }
+    }
+}
 
-            //Draw from a baseline to highest one above highest for 1px gap. This may be eq or above the base line, in which case nothing gets drawn
-            for y in baseline..(highest - 1) {
-                buffer.put_pixel(x, y, PIXEL_WHITE);
-            }
+impl FromStr for Backend {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Backend, SimpleError> {
+        match s {
+            "ssd1305" => Ok(Backend::Ssd1305),
+            "ssd1306" => Ok(Backend::Ssd1306),
+            "sh1106" => Ok(Backend::Sh1106),
+            "image-writer" => Ok(Backend::ImageWriter),
+            "terminal" => Ok(Backend::Terminal),
+            "preview-server" => Ok(Backend::PreviewServer),
+            "framebuffer" => Ok(Backend::Framebuffer),
+            _ => Err(SimpleError::new(format!(
+                "Unknown display backend `{}`, expected one of ssd1305, ssd1306, sh1106, image-writer, terminal, preview-server, framebuffer",
+                s
+            ))),
         }
     }
 }
 
-pub struct WaterMark<'a> {
-    pos: Position,
-    bounds: Bounds,
-    tide_model: &'a TideModel,
+#[cfg(feature = "image-writer")]
+pub struct ImageWriter {
+    width: u32,
+    height: u32,
+    path: PathBuf,
+    controls: EmulatedControls,
 }
 
-impl WaterMark<'_> {
-    pub fn new(x: u32, y: u32, w: u32, h: u32, tide_model: &TideModel) -> WaterMark {
-        WaterMark {
-            pos: Position { x, y },
-            bounds: Bounds { w, h },
-            tide_model,
+#[cfg(feature = "image-writer")]
+impl ImageWriter {
+    // The clock saves to Paths::display_image
+    pub fn with_path(width: u32, height: u32, path: PathBuf) -> ImageWriter {
+        ImageWriter {
+            width,
+            height,
+            path,
+            controls: EmulatedControls::default(),
         }
     }
 }
 
-impl Painter for WaterMark<'_> {
-    fn paint(&self, buffer: &mut RgbImage, now: DateTime<Utc>) {
-        //Draw upper + lower notch
-        buffer.put_pixel(self.pos.x, self.pos.y, PIXEL_WHITE);
-        buffer.put_pixel(self.pos.x, self.pos.y + self.bounds.h - 1, PIXEL_WHITE);
-
-        //Draw bar
-        for row in 0..self.bounds.h {
-            buffer.put_pixel(self.pos.x + 1_u32, self.pos.y + row, PIXEL_WHITE);
-        }
+#[cfg(feature = "image-writer")]
+impl RenderDevice for ImageWriter {
+    fn render(&mut self, buffer: &MonoFrame) -> Result<(), Box<dyn Error>> {
+        self.controls.to_rgb(buffer).save(&self.path)?;
+        Ok(())
+    }
 
-        //Draw water mark
-        let t = self.tide_model.get_current_norm_height(now);
-        let y_pos: u32 = maths::lerp(
-            t,
-            (self.pos.y + self.bounds.h - 1) as i32,
-            self.pos.y as i32,
-        ) as u32;
-
-        let mark_y = y_pos;
-        let mark_x: u32 = match mark_y == self.pos.y || mark_y == self.pos.y + self.bounds.h - 1 {
-            true => self.pos.x - 1, //Offset by 1 pixel if at upper or lower notch
-            false => self.pos.x,
-        };
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
 
-        buffer.put_pixel(mark_x, mark_y, PIXEL_WHITE);
+    fn controls(&mut self) -> Option<&mut dyn DisplayControl> {
+        Some(&mut self.controls)
     }
 }
 
@@ -371,94 +335,52 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_should_erase() {
-        let kernel = [0, 1, 1, 1, 1, 1, 1, 1, 1];
-
-        assert_eq!(should_erase(kernel, &FLOOD_FILL_MASK), true);
+    fn test_backend_names() {
+        for backend in &[
+            Backend::Ssd1305,
+            Backend::Ssd1306,
+            Backend::Sh1106,
+            Backend::ImageWriter,
+            Backend::Terminal,
+            Backend::PreviewServer,
+            Backend::Framebuffer,
+        ] {
+            assert_eq!(backend.name().parse::<Backend>().unwrap(), *backend);
+        }
+        assert!("bmp".parse::<Backend>().is_err());
 
-        let kernel = [1, 1, 0, 1, 1, 1, 1, 1, 1];
-        assert_eq!(should_erase(kernel, &FLOOD_FILL_MASK), true);
+        let settings: crate::settings::DisplaySettings =
+            toml::from_str("width = 128\nheight = 32\nbackend = \"image-writer\"").unwrap();
+        assert_eq!(settings.backend, Some(Backend::ImageWriter));
     }
 
     #[test]
-    fn test_calculate_pixel() {
-        // 0 0 0 1
-        // 0 0 1 1
-        // 0 1 1 1
-        // 1 1 1 1
-        let normalized_heights: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
-        let bounds = Bounds { w: 4, h: 4 };
-
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 0), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 0), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 2, 0), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 3, 0), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 1), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 1), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 2, 1), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 3, 1), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 2), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 2), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 2, 2), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 3, 2), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 3), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 3), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 2, 3), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 3, 3), 1);
-
-        // 0 0 0 0 0 1
-        // 0 0 0 0 0 1
-        // 0 0 0 0 1 1
-        // 0 0 0 0 1 1
-        // 0 0 0 1 1 1
-        // 0 0 0 1 1 1
-        // 0 0 1 1 1 1
-        // 0 0 1 1 1 1
-        // 0 1 1 1 1 1
-        // 0 1 1 1 1 1
-        let normalized_heights: [f32; 6] = [0_f32, 0.2, 0.4, 0.6, 0.8, 1_f32];
-        let bounds = Bounds { w: 6, h: 10 };
-
-        // x 0 0 0 0 0 1
-        // x 0 0 0 0 0 1
-        // x 0 0 0 0 1 1
-        // x 0 0 0 0 1 1
-        // x 0 0 0 1 1 1
-        // x 0 0 0 1 1 1
-        // x 0 0 1 1 1 1
-        // x 0 0 1 1 1 1
-        //|x 0 1|1 1 1 1
-        //|x 0 1|1 1 1 1
-        //|x x x|x x x x
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, -1, 8), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 8), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 8), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, -1, 9), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 9), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 9), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, -1, 10), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 0, 10), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 1, 10), 1);
-
-        // x x x x x x x x
-        // 0 0 0 0|0 1 x|x
-        // 0 0 0 0|0 1 x|x
-        // 0 0 0 0|1 1 x|x
-        // 0 0 0 0 1 1 x x
-        // 0 0 0 1 1 1 x x
-        // 0 0 0 1 1 1 x x
-        // 0 0 1 1 1 1 x x
-        // 0 0 1 1 1 1 x x
-        // 0 1 1 1 1 1 x x
-        // 0 1 1 1 1 1 x x
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 4, 0), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 5, 0), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 6, 0), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 4, 1), 0);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 5, 1), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 6, 1), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 4, 2), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 5, 2), 1);
-        assert_eq!(calculate_pixel(&normalized_heights, &bounds, 6, 2), 1);
+    fn test_emulated_controls() {
+        let frame = MonoFrame::from_fn(3, 2, |x, y| x == 0 && y == 0);
+        let mut controls = EmulatedControls::default();
+        assert_eq!(controls.apply(&frame), frame);
+
+        controls.set_rotated(true).unwrap();
+        assert_eq!(
+            controls.apply(&frame),
+            MonoFrame::from_fn(3, 2, |x, y| x == 2 && y == 1)
+        );
+
+        controls.set_inverted(true).unwrap();
+        assert!(!controls.apply(&frame).get_pixel(2, 1));
+        assert!(controls.apply(&frame).get_pixel(0, 0));
+
+        controls.set_display_on(false).unwrap();
+        assert_eq!(controls.apply(&frame), MonoFrame::new(3, 2));
+
+        let mut controls = EmulatedControls::default();
+        controls.set_contrast(0).unwrap();
+        assert_eq!(controls.to_rgb(&frame).get_pixel(0, 0), &Rgb([64, 64, 64]));
+        controls.set_contrast(0x80).unwrap();
+        assert_eq!(
+            controls.to_rgb(&frame).get_pixel(0, 0),
+            &Rgb([255, 255, 255])
+        );
+        assert_eq!(controls.to_rgb(&frame).get_pixel(1, 0), &Rgb([0, 0, 0]));
     }
 }
@@ -1,80 +1,86 @@
-use image::{GenericImageView, RgbImage};
-use std::collections::HashMap;
+use crate::mono_frame::MonoFrame;
+use crate::paths::Paths;
+use image::DynamicImage;
+use simple_error::SimpleError;
+use std::error::Error;
 use std::path::Path;
+pub use tide_clock_core::font::Font5;
 
-pub struct Font5 {
-    pub faces: HashMap<char, RgbImage>,
-}
+//Compiled in so a copied binary can always draw, whatever the working directory
+const EMBEDDED: &[u8] = include_bytes!("../resources/Font-5px.png");
 
-impl Font5 {
-    pub fn new() -> Font5 {
-        let p = Path::new("resources/Font-5px.png");
+// Font-5px.png from the assets directory when there is one, otherwise the built in font.
+// A sheet that won't load is reported and the built in font used instead
+pub fn load(paths: &Paths) -> Font5 {
+    let p = paths.font();
+    if !p.exists() {
+        return embedded();
+    }
 
-        let img = image::open(p).unwrap().to_rgb();
+    match load_file(&p) {
+        Ok(font) => font,
+        Err(e) => {
+            println!("{}. Using the built in font", e);
+            embedded()
+        }
+    }
+}
 
-        let mut faces = HashMap::new();
+pub fn load_file(p: &Path) -> Result<Font5, Box<dyn Error>> {
+    let img = image::open(p)
+        .map_err(|e| SimpleError::new(format!("Could not load the font {:?}. Err {}", p, e)))?;
 
-        //So in theory it's not necessary to call img.view().to_image() and instead to have a memory effecient reference to the original image. aka img.view()
-        //However rust starts complaining about the subimage outliving the lifetime of the original image.
-        //And quite simply I don't know enough rust to fix that
-        //Something something lifetime annotations... but that's as far as I got.
-        //However the error won't look so lush, it will say something about expectect struct got reference
-        //https://squidarth.com/rc/rust/2018/05/31/rust-borrowing-and-ownership.html
+    Ok(from_image(img))
+}
 
-        faces.insert(' ', img.view(124, 0, 2, 5).to_image());
-        faces.insert('_', img.view(124, 0, 1, 5).to_image());
-        faces.insert('1', img.view(0, 0, 1, 5).to_image());
-        faces.insert('2', img.view(2, 0, 3, 5).to_image());
-        faces.insert('3', img.view(6, 0, 3, 5).to_image());
-        faces.insert('4', img.view(10, 0, 3, 5).to_image());
-        faces.insert('5', img.view(14, 0, 3, 5).to_image());
-        faces.insert('6', img.view(18, 0, 3, 5).to_image());
-        faces.insert('7', img.view(22, 0, 3, 5).to_image());
-        faces.insert('8', img.view(26, 0, 3, 5).to_image());
-        faces.insert('9', img.view(30, 0, 3, 5).to_image());
-        faces.insert('0', img.view(34, 0, 3, 5).to_image());
-        faces.insert(':', img.view(38, 0, 1, 5).to_image());
-        faces.insert('.', img.view(40, 0, 1, 5).to_image());
-        faces.insert('m', img.view(42, 0, 5, 5).to_image());
-        faces.insert('f', img.view(48, 0, 2, 5).to_image());
-        faces.insert('t', img.view(51, 0, 2, 5).to_image());
-        faces.insert('!', img.view(54, 0, 1, 5).to_image());
-        faces.insert('?', img.view(56, 0, 3, 5).to_image());
-        faces.insert('A', img.view(0, 6, 3, 5).to_image());
-        faces.insert('B', img.view(4, 6, 3, 5).to_image());
-        faces.insert('C', img.view(8, 6, 3, 5).to_image());
-        faces.insert('D', img.view(12, 6, 3, 5).to_image());
-        faces.insert('E', img.view(16, 6, 3, 5).to_image());
-        faces.insert('F', img.view(20, 6, 3, 5).to_image());
-        faces.insert('G', img.view(24, 6, 3, 5).to_image());
-        faces.insert('H', img.view(28, 6, 3, 5).to_image());
-        faces.insert('I', img.view(32, 6, 1, 5).to_image());
-        faces.insert('J', img.view(34, 6, 3, 5).to_image());
-        faces.insert('K', img.view(38, 6, 3, 5).to_image());
-        faces.insert('L', img.view(42, 6, 3, 5).to_image());
-        faces.insert('M', img.view(46, 6, 5, 5).to_image());
-        faces.insert('N', img.view(52, 6, 3, 5).to_image());
-        faces.insert('O', img.view(56, 6, 3, 5).to_image());
-        faces.insert('P', img.view(60, 6, 3, 5).to_image());
-        faces.insert('Q', img.view(64, 6, 4, 5).to_image());
-        faces.insert('R', img.view(69, 6, 3, 5).to_image());
-        faces.insert('S', img.view(73, 6, 3, 5).to_image());
-        faces.insert('T', img.view(77, 6, 3, 5).to_image());
-        faces.insert('U', img.view(81, 6, 3, 5).to_image());
-        faces.insert('V', img.view(85, 6, 3, 5).to_image());
-        faces.insert('W', img.view(89, 6, 5, 5).to_image());
-        faces.insert('X', img.view(95, 6, 3, 5).to_image());
-        faces.insert('Y', img.view(99, 6, 3, 5).to_image());
-        faces.insert('Z', img.view(103, 6, 3, 5).to_image());
-        faces.insert('(', img.view(0, 12, 6, 6).to_image());
-        faces.insert(')', img.view(7, 12, 6, 6).to_image());
-        faces.insert('[', img.view(14, 12, 6, 6).to_image());
-        faces.insert(']', img.view(21, 12, 6, 6).to_image());
+pub fn embedded() -> Font5 {
+    //Can't fail, the same bytes are loaded by test_embedded_font
+    from_image(image::load_from_memory(EMBEDDED).unwrap())
+}
 
-        Font5 { faces }
-    }
+fn from_image(img: DynamicImage) -> Font5 {
+    Font5::from_sheet(&MonoFrame::from_rgb(&img.to_rgb()))
 }
 
 pub fn init() -> Font5 {
-    Font5::new()
+    embedded()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_embedded_font() {
+        let font = embedded();
+        assert_eq!(
+            font.faces,
+            load_file(Path::new("resources/Font-5px.png"))
+                .unwrap()
+                .faces
+        );
+        assert!(font.faces.contains_key(&'A'));
+    }
+
+    #[test]
+    fn test_missing_or_broken_override() {
+        let missing = Paths {
+            assets_dir: PathBuf::from("does/not/exist"),
+            ..Paths::default()
+        };
+        assert_eq!(load(&missing).faces, embedded().faces);
+
+        //A sheet that isn't a PNG falls back too
+        let dir = std::env::temp_dir().join(format!("tide-clock-font-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Font-5px.png"), "not a png").unwrap();
+        let broken = Paths {
+            assets_dir: dir.clone(),
+            ..Paths::default()
+        };
+        assert!(load_file(&broken.font()).is_err());
+        assert_eq!(load(&broken).faces, embedded().faces);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,372 @@
+use crate::display::{DisplayControl, EmulatedControls, RenderDevice};
+use crate::mono_frame::MonoFrame;
+use serde::{Deserialize, Serialize};
+use simple_error::SimpleError;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// The [display.framebuffer] section of Settings.toml
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct FramebufferSettings {
+    #[serde(default = "default_device")]
+    pub device: PathBuf,
+    //Size of each pixel of the frame on the screen, the largest that fits when not set
+    #[serde(default)]
+    pub scale: Option<u32>,
+    //RGB of lit pixels, dimmed with the contrast, and of the rest of the screen
+    #[serde(default = "default_foreground")]
+    pub foreground: [u8; 3],
+    #[serde(default)]
+    pub background: [u8; 3],
+    //The geometry is read from /sys/class/graphics/fbN, these override it for drivers that
+    //report it wrong or systems without sysfs
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    //Bytes per line, including any padding at the end
+    #[serde(default)]
+    pub stride: Option<u32>,
+    #[serde(default)]
+    pub bits_per_pixel: Option<u32>,
+}
+
+fn default_device() -> PathBuf {
+    PathBuf::from("/dev/fb1")
+}
+
+fn default_foreground() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+impl Default for FramebufferSettings {
+    fn default() -> FramebufferSettings {
+        FramebufferSettings {
+            device: default_device(),
+            scale: None,
+            foreground: default_foreground(),
+            background: [0, 0, 0],
+            width: None,
+            height: None,
+            stride: None,
+            bits_per_pixel: None,
+        }
+    }
+}
+
+// Visible size and memory layout of a framebuffer
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Geometry {
+    pub width: u32,
+    pub height: u32,
+    pub stride: u32,
+    pub bits_per_pixel: u32,
+}
+
+impl Geometry {
+    // From the sysfs directory of the device, e.g. /sys/class/graphics/fb1. Settings that are
+    // set win over what the driver reports, if everything is set sysfs isn't read at all
+    pub fn load(sysfs: &Path, settings: &FramebufferSettings) -> Result<Geometry, Box<dyn Error>> {
+        let read = |name: &str| {
+            fs::read_to_string(sysfs.join(name)).map_err(|err| {
+                SimpleError::new(format!("{} Filename {}:", err, sysfs.join(name).display()))
+            })
+        };
+        let number =
+            |name: &str| -> Result<u32, Box<dyn Error>> { Ok(read(name)?.trim().parse()?) };
+
+        let (width, height) = match (settings.width, settings.height) {
+            (Some(width), Some(height)) => (width, height),
+            _ => {
+                //"480,320"
+                let size = read("virtual_size")?;
+                let mut size = size.trim().split(',').map(|n| n.parse::<u32>());
+                match (size.next(), size.next()) {
+                    (Some(Ok(width)), Some(Ok(height))) => (
+                        settings.width.unwrap_or(width),
+                        settings.height.unwrap_or(height),
+                    ),
+                    _ => return Err(SimpleError::new("Could not read the framebuffer size").into()),
+                }
+            }
+        };
+        let bits_per_pixel = match settings.bits_per_pixel {
+            Some(bpp) => bpp,
+            None => number("bits_per_pixel")?,
+        };
+        let stride = match settings.stride {
+            Some(stride) => stride,
+            None => number("stride")?,
+        };
+
+        Ok(Geometry {
+            width,
+            height,
+            stride,
+            bits_per_pixel,
+        })
+    }
+}
+
+// A colour as it's stored in the framebuffer. 16 bit is RGB565, 24 and 32 bit are BGR and
+// BGRX, the usual layouts of the small SPI TFT drivers
+fn encode(rgb: [u8; 3], bits_per_pixel: u32) -> Result<Vec<u8>, SimpleError> {
+    let [r, g, b] = rgb;
+    match bits_per_pixel {
+        16 => {
+            let pixel = (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3;
+            Ok(pixel.to_le_bytes().to_vec())
+        }
+        24 => Ok(vec![b, g, r]),
+        32 => Ok(vec![b, g, r, 0xFF]),
+        _ => Err(SimpleError::new(format!(
+            "Framebuffers with {} bits per pixel aren't supported",
+            bits_per_pixel
+        ))),
+    }
+}
+
+// Writes frames to a Linux framebuffer device, scaled up and centered on the screen
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    file: File,
+    geometry: Geometry,
+    scale: u32,
+    foreground: [u8; 3],
+    background: [u8; 3],
+    controls: EmulatedControls,
+}
+
+impl Framebuffer {
+    pub fn open(
+        width: u32,
+        height: u32,
+        settings: &FramebufferSettings,
+    ) -> Result<Framebuffer, Box<dyn Error>> {
+        let name = settings.device.file_name().unwrap_or_default();
+        let sysfs = Path::new("/sys/class/graphics").join(name);
+        let geometry = Geometry::load(&sysfs, settings)?;
+
+        Framebuffer::with_geometry(width, height, settings, geometry)
+    }
+
+    pub fn with_geometry(
+        width: u32,
+        height: u32,
+        settings: &FramebufferSettings,
+        geometry: Geometry,
+    ) -> Result<Framebuffer, Box<dyn Error>> {
+        //Fail now rather than on the first frame
+        encode(settings.background, geometry.bits_per_pixel)?;
+        if geometry.stride < geometry.width * geometry.bits_per_pixel / 8 {
+            return Err(SimpleError::new("The framebuffer stride is shorter than a line").into());
+        }
+
+        let fits = (geometry.width / width)
+            .min(geometry.height / height)
+            .max(1);
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&settings.device)
+            .map_err(|err| {
+                SimpleError::new(format!("{} Filename {}:", err, settings.device.display()))
+            })?;
+
+        Ok(Framebuffer {
+            width,
+            height,
+            file,
+            geometry,
+            scale: settings.scale.unwrap_or(fits).max(1),
+            foreground: settings.foreground,
+            background: settings.background,
+            controls: EmulatedControls::default(),
+        })
+    }
+
+    // The whole screen as stored in the framebuffer
+    fn draw(&self, frame: &MonoFrame) -> Result<Vec<u8>, Box<dyn Error>> {
+        let Geometry {
+            width,
+            height,
+            stride,
+            bits_per_pixel,
+        } = self.geometry;
+        let bytes_per_pixel = (bits_per_pixel / 8) as usize;
+
+        let level = self.controls.level() as u32;
+        let dim = |c: u8| (c as u32 * level / 255) as u8;
+        let [r, g, b] = self.foreground;
+        let lit = encode([dim(r), dim(g), dim(b)], bits_per_pixel)?;
+        let background = encode(self.background, bits_per_pixel)?;
+
+        let mut screen = Vec::with_capacity((stride * height) as usize);
+        for _ in 0..height {
+            let line = screen.len();
+            for _ in 0..width {
+                screen.extend_from_slice(&background);
+            }
+            screen.resize(line + stride as usize, 0);
+        }
+
+        //Centered, anything that doesn't fit is cut off
+        let left = width.saturating_sub(frame.width() * self.scale) / 2;
+        let top = height.saturating_sub(frame.height() * self.scale) / 2;
+        for y in top..height.min(top + frame.height() * self.scale) {
+            for x in left..width.min(left + frame.width() * self.scale) {
+                if frame.get_pixel((x - left) / self.scale, (y - top) / self.scale) {
+                    let at = (y * stride) as usize + x as usize * bytes_per_pixel;
+                    screen[at..at + bytes_per_pixel].copy_from_slice(&lit);
+                }
+            }
+        }
+
+        Ok(screen)
+    }
+}
+
+impl RenderDevice for Framebuffer {
+    fn render(&mut self, buffer: &MonoFrame) -> Result<(), Box<dyn Error>> {
+        let screen = self.draw(&self.controls.apply(buffer))?;
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&screen)?;
+        self.file.flush()?;
+
+        Ok(())
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn controls(&mut self) -> Option<&mut dyn DisplayControl> {
+        Some(&mut self.controls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A regular file standing in for the device
+    fn device(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("fb-{}-{}", name, std::process::id()));
+        File::create(&path).unwrap();
+        path
+    }
+
+    fn settings(device: PathBuf) -> FramebufferSettings {
+        FramebufferSettings {
+            device,
+            foreground: [255, 128, 0],
+            ..FramebufferSettings::default()
+        }
+    }
+
+    fn geometry(width: u32, height: u32, stride: u32, bits_per_pixel: u32) -> Geometry {
+        Geometry {
+            width,
+            height,
+            stride,
+            bits_per_pixel,
+        }
+    }
+
+    #[test]
+    fn test_scaled_rgb565() {
+        let path = device("rgb565");
+        let settings = settings(path.clone());
+        let mut fb =
+            Framebuffer::with_geometry(128, 32, &settings, geometry(320, 240, 640, 16)).unwrap();
+        let mut frame = MonoFrame::new(128, 32);
+        frame.set_pixel(0, 0, true);
+
+        fb.render(&frame).unwrap();
+
+        //Scaled 2x and centered, the pixel covers 32..34, 88..90
+        let screen = fs::read(&path).unwrap();
+        assert_eq!(screen.len(), 640 * 240);
+        let pixel = |x: usize, y: usize| {
+            u16::from_le_bytes([screen[y * 640 + x * 2], screen[y * 640 + x * 2 + 1]])
+        };
+        //RGB565 of 255, 128, 0
+        let orange = 0b1111_1100_0000_0000;
+        assert_eq!(pixel(32, 88), orange);
+        assert_eq!(pixel(33, 89), orange);
+        assert_eq!(pixel(34, 88), 0);
+        assert_eq!(pixel(31, 88), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stride_padding_and_bgrx() {
+        let path = device("bgrx");
+        let mut settings = settings(path.clone());
+        settings.scale = Some(1);
+        settings.background = [0, 0, 64];
+        //Lines padded from 4 * 130 to 544 bytes
+        let mut fb =
+            Framebuffer::with_geometry(128, 32, &settings, geometry(130, 34, 544, 32)).unwrap();
+        let mut frame = MonoFrame::new(128, 32);
+        frame.set_pixel(0, 0, true);
+
+        fb.render(&frame).unwrap();
+
+        let screen = fs::read(&path).unwrap();
+        assert_eq!(screen.len(), 544 * 34);
+        assert_eq!(&screen[0..4], &[64, 0, 0, 0xFF]);
+        assert_eq!(&screen[544 + 4..544 + 8], &[0, 128, 255, 0xFF]);
+        assert_eq!(&screen[520..544], &[0; 24][..]);
+
+        //Turned down contrast dims the foreground
+        fb.controls().unwrap().set_contrast(0).unwrap();
+        fb.render(&frame).unwrap();
+        let screen = fs::read(&path).unwrap();
+        assert_eq!(&screen[544 + 4..544 + 8], &[0, 32, 64, 0xFF]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_geometry_from_sysfs() {
+        let sysfs = std::env::temp_dir().join(format!("fb-sysfs-{}", std::process::id()));
+        fs::create_dir_all(&sysfs).unwrap();
+        fs::write(sysfs.join("virtual_size"), "480,320\n").unwrap();
+        fs::write(sysfs.join("stride"), "960\n").unwrap();
+        fs::write(sysfs.join("bits_per_pixel"), "16\n").unwrap();
+
+        let settings = FramebufferSettings::default();
+        assert_eq!(
+            Geometry::load(&sysfs, &settings).unwrap(),
+            geometry(480, 320, 960, 16)
+        );
+
+        //Overrides win
+        let settings = FramebufferSettings {
+            height: Some(300),
+            bits_per_pixel: Some(32),
+            ..FramebufferSettings::default()
+        };
+        assert_eq!(
+            Geometry::load(&sysfs, &settings).unwrap(),
+            geometry(480, 300, 960, 32)
+        );
+
+        fs::remove_dir_all(&sysfs).unwrap();
+        assert!(Geometry::load(&sysfs, &FramebufferSettings::default()).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        let settings = settings(device("8bit"));
+        assert!(
+            Framebuffer::with_geometry(128, 32, &settings, geometry(320, 240, 320, 8)).is_err()
+        );
+        fs::remove_file(&settings.device).unwrap();
+    }
+}
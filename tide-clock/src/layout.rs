@@ -0,0 +1,926 @@
+use crate::display::{
+    ChangesAt, GraphCanvas, Painter, TextField, TextList, TimeShift, Transition, WaterMark,
+};
+use crate::font::Font5;
+use crate::mono_frame::MonoFrame;
+use crate::night::NightMode;
+use crate::tides::{self, ExtremeKind, TideModel, TideModelWindow};
+use crate::{schedule, settings};
+use chrono::{DateTime, Duration, Local, Offset, Utc};
+use serde::Deserialize;
+use std::{error::Error, path::Path};
+
+const LINE_HEIGHT: u32 = 8;
+
+// Describes the pages of the clock face and the widgets on each. Loaded once at startup,
+// the painters themselves are rebuilt every frame as they borrow the current tide window
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Layout {
+    #[serde(default)]
+    pub rotation: Rotation,
+    #[serde(rename = "page")]
+    pub pages: Vec<Page>,
+    //Replaces the rotation while the night mode is "minimal"
+    #[serde(default = "Page::night")]
+    pub night: Page,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Rotation {
+    #[serde(default)]
+    pub transition: Transition,
+    #[serde(default = "default_transition_frames")]
+    pub transition_frames: u32,
+    #[serde(default = "default_transition_frame_ms")]
+    pub transition_frame_ms: u64,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Page {
+    #[serde(default)]
+    pub name: String,
+    //How long the page stays up before rotating to the next one
+    #[serde(default = "default_page_seconds")]
+    pub seconds: u32,
+    #[serde(rename = "widget", default)]
+    pub widgets: Vec<Widget>,
+}
+
+// Widgets are painted in the order they are listed. Note the graph should come after anything
+// it overlaps, its labels look at the wave pixels to work out how long the descenders are.
+// Negative positions are measured from the right or bottom edge of the screen, and a missing
+// width or height stretches the widget to that edge, so one layout can serve several panel sizes
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Widget {
+    Clock {
+        x: i32,
+        y: i32,
+        #[serde(default = "default_blink")]
+        blink: bool,
+    },
+    //The window's high and low water, or with offset_hours the whole of that day's, to go
+    //with a graph shifted the same way
+    HighWater {
+        x: i32,
+        y: i32,
+        #[serde(default)]
+        offset_hours: i64,
+    },
+    LowWater {
+        x: i32,
+        y: i32,
+        #[serde(default)]
+        offset_hours: i64,
+    },
+    Text {
+        x: i32,
+        y: i32,
+        text: String,
+    },
+    WaterMark {
+        x: i32,
+        y: i32,
+        #[serde(default)]
+        w: Option<u32>,
+        #[serde(default)]
+        h: Option<u32>,
+    },
+    Graph {
+        x: i32,
+        y: i32,
+        #[serde(default)]
+        w: Option<u32>,
+        #[serde(default)]
+        h: Option<u32>,
+        //Shift the graph into the future, 24 shows tomorrow's tides
+        #[serde(default)]
+        offset_hours: i64,
+    },
+    //Upcoming high and low tides, one per line
+    Extremes {
+        x: i32,
+        y: i32,
+        #[serde(default = "default_rows")]
+        rows: usize,
+    },
+    //High, low, mean and range of the water over the coming hours
+    Statistics {
+        x: i32,
+        y: i32,
+        #[serde(default = "default_statistics_hours")]
+        hours: i64,
+    },
+}
+
+fn default_blink() -> bool {
+    true
+}
+
+fn default_rows() -> usize {
+    4
+}
+
+fn default_statistics_hours() -> i64 {
+    24
+}
+
+fn default_page_seconds() -> u32 {
+    30
+}
+
+fn default_transition_frames() -> u32 {
+    8
+}
+
+fn default_transition_frame_ms() -> u64 {
+    40
+}
+
+pub struct FrameContext<'a> {
+    pub screen: (u32, u32),
+    pub font: &'a Font5,
+    pub tide_model: &'a TideModel,
+    pub tide_window: &'a TideModelWindow<'a>,
+    pub local_time: DateTime<Local>,
+}
+
+impl Layout {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Layout, Box<dyn Error>> {
+        let layout: Layout = settings::load_config(&path)?;
+
+        if layout.pages.is_empty() {
+            return Err(Box::new(simple_error::SimpleError::new(format!(
+                "{} does not define any pages",
+                path.as_ref().display()
+            ))));
+        }
+
+        //Off the edge of the screen is clipped, but there's nothing to draw in no space at all
+        if let Some(page) = layout
+            .pages
+            .iter()
+            .find(|p| p.widgets.iter().any(Widget::is_empty))
+        {
+            return Err(Box::new(simple_error::SimpleError::new(format!(
+                "{}: page `{}` has a graph or water mark with no width or height",
+                path.as_ref().display(),
+                page.name
+            ))));
+        }
+
+        Ok(layout)
+    }
+
+    // Samples from the start of the current window that the graphs need, which decides when
+    // the tide data is topped up. A graph shifted into the future also needs the samples it
+    // skips over, `sample_interval` apart
+    pub fn samples_needed(&self, screen: (u32, u32), sample_interval: Option<Duration>) -> u32 {
+        let interval = sample_interval.map_or(0, |i| i.num_seconds());
+
+        self.pages
+            .iter()
+            .flat_map(|p| p.widgets.iter())
+            .filter_map(|w| match *w {
+                Widget::Graph {
+                    x,
+                    y,
+                    w,
+                    h,
+                    offset_hours,
+                } => {
+                    let skipped = match interval {
+                        0 => 0,
+                        _ => (offset_hours.max(0) * 3600 + interval - 1) / interval,
+                    };
+                    Some(Rect::resolve(screen, x, y, w, h).w + skipped as u32)
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Widest graph showing the current tides
+    pub fn graph_width(&self, screen: (u32, u32)) -> u32 {
+        self.pages
+            .iter()
+            .flat_map(|p| p.widgets.iter())
+            .filter_map(|w| match *w {
+                Widget::Graph {
+                    x,
+                    y,
+                    w,
+                    h,
+                    offset_hours: 0,
+                } => Some(Rect::resolve(screen, x, y, w, h).w),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    // The night page counts as one past the last page of the rotation, so switching to it
+    // plays a transition like any other page change
+    pub fn night_page_index(&self) -> usize {
+        self.pages.len()
+    }
+
+    // The night page while the night mode is minimal, otherwise wherever the rotation is
+    pub fn current_page_index(
+        &self,
+        night_mode: Option<NightMode>,
+        local_time: DateTime<Local>,
+    ) -> usize {
+        match night_mode {
+            Some(NightMode::Minimal) => self.night_page_index(),
+            _ => self.page_index_at(local_time),
+        }
+    }
+
+    pub fn page(&self, index: usize) -> &Page {
+        self.pages.get(index).unwrap_or(&self.night)
+    }
+
+    // Pages rotate on the wall clock rather than from when the program started, so several
+    // clocks in a room stay in step with each other
+    pub fn page_index_at(&self, local_time: DateTime<Local>) -> usize {
+        let cycle: i64 = self.pages.iter().map(|p| p.seconds.max(1) as i64).sum();
+        if cycle == 0 {
+            return 0;
+        }
+
+        let mut elapsed = local_time.timestamp().rem_euclid(cycle);
+        for (index, page) in self.pages.iter().enumerate() {
+            let seconds = page.seconds.max(1) as i64;
+            if elapsed < seconds {
+                return index;
+            }
+            elapsed -= seconds;
+        }
+
+        0
+    }
+
+    // When page_index_at moves on to the next page, None when there's nothing to rotate
+    pub fn next_page_change(&self, local_time: DateTime<Local>) -> Option<DateTime<Utc>> {
+        if self.pages.len() < 2 {
+            return None;
+        }
+
+        let cycle: i64 = self.pages.iter().map(|p| p.seconds.max(1) as i64).sum();
+        let elapsed = local_time.timestamp().rem_euclid(cycle);
+        let mut page_end = 0;
+        for page in &self.pages {
+            page_end += page.seconds.max(1) as i64;
+            if elapsed < page_end {
+                break;
+            }
+        }
+
+        //From the start of the current second, to land on the boundary
+        let second = schedule::next_boundary(&local_time, 1) - Duration::seconds(1);
+        Some(second + Duration::seconds(page_end - elapsed))
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            rotation: Rotation::default(),
+            pages: vec![Page::default()],
+            night: Page::night(),
+        }
+    }
+}
+
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation {
+            transition: Transition::None,
+            transition_frames: default_transition_frames(),
+            transition_frame_ms: default_transition_frame_ms(),
+        }
+    }
+}
+
+impl Page {
+    // Just the time, for when the layout doesn't define a night page
+    pub fn night() -> Page {
+        Page {
+            name: "night".to_string(),
+            seconds: default_page_seconds(),
+            widgets: vec![Widget::Clock {
+                x: 0,
+                y: 0,
+                blink: false,
+            }],
+        }
+    }
+
+    pub fn painters<'a>(&self, ctx: &FrameContext<'a>) -> Vec<Box<dyn Painter + 'a>> {
+        self.widgets.iter().map(|w| w.build(ctx)).collect()
+    }
+
+    // The next time any widget on the page draws something different
+    pub fn next_change(&self, ctx: &FrameContext) -> Option<DateTime<Utc>> {
+        let utc_now = tides::local_to_utc(ctx.local_time);
+
+        self.painters(ctx)
+            .iter()
+            .filter_map(|p| p.next_change(utc_now))
+            .min()
+    }
+}
+
+impl Default for Page {
+    // The original face, designed for 128x32
+    fn default() -> Page {
+        Page {
+            name: "graph".to_string(),
+            seconds: default_page_seconds(),
+            widgets: vec![
+                Widget::Clock {
+                    x: 0,
+                    y: 0,
+                    blink: true,
+                },
+                Widget::HighWater {
+                    x: 0,
+                    y: 8,
+                    offset_hours: 0,
+                },
+                Widget::LowWater {
+                    x: 0,
+                    y: -5,
+                    offset_hours: 0,
+                },
+                Widget::WaterMark {
+                    x: 17,
+                    y: 10,
+                    w: Some(2),
+                    h: None,
+                },
+                Widget::Graph {
+                    x: 21,
+                    y: 10,
+                    w: None,
+                    h: None,
+                    offset_hours: 0,
+                },
+            ],
+        }
+    }
+}
+
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+impl Rect {
+    fn resolve(screen: (u32, u32), x: i32, y: i32, w: Option<u32>, h: Option<u32>) -> Rect {
+        let x = resolve_axis(screen.0, x);
+        let y = resolve_axis(screen.1, y);
+        //Nothing hangs off the right or bottom edge, so the painters only see what's on screen
+        let (max_w, max_h) = (screen.0.saturating_sub(x), screen.1.saturating_sub(y));
+
+        Rect {
+            x,
+            y,
+            w: w.unwrap_or(max_w).min(max_w),
+            h: h.unwrap_or(max_h).min(max_h),
+        }
+    }
+}
+
+fn resolve_axis(length: u32, position: i32) -> u32 {
+    if position < 0 {
+        length.saturating_sub(position.unsigned_abs())
+    } else {
+        position as u32
+    }
+}
+
+impl Widget {
+    fn is_empty(&self) -> bool {
+        match *self {
+            Widget::WaterMark { w, h, .. } | Widget::Graph { w, h, .. } => {
+                w == Some(0) || h == Some(0)
+            }
+            _ => false,
+        }
+    }
+
+    fn build<'a>(&self, ctx: &FrameContext<'a>) -> Box<dyn Painter + 'a> {
+        let screen = ctx.screen;
+
+        match *self {
+            Widget::Clock { x, y, blink } => {
+                let format = match blink && ctx.local_time.timestamp() % 2 == 1 {
+                    true => "%H_%M", //'_' Will be substituted for 1px space, instead of 2px space as used for words
+                    false => "%H:%M",
+                };
+                let text = ctx.local_time.format(format).to_string();
+                //Every second while the colon blinks, otherwise on the minute
+                let period = match blink {
+                    true => 1,
+                    false => 60,
+                };
+                let changes = schedule::next_boundary(&ctx.local_time, period);
+
+                Box::new(ChangesAt::new(Some(changes), text_field(text, ctx, x, y)))
+            }
+            //The window moves along a sample at a time, which can change its high and low water
+            Widget::HighWater { x, y, offset_hours } => {
+                let text = level_text(water_levels(ctx, offset_hours).map(|(high, _)| high));
+
+                Box::new(ChangesAt::new(
+                    next_sample_change(ctx),
+                    text_field(text, ctx, x, y),
+                ))
+            }
+            Widget::LowWater { x, y, offset_hours } => {
+                let text = level_text(water_levels(ctx, offset_hours).map(|(_, low)| low));
+
+                Box::new(ChangesAt::new(
+                    next_sample_change(ctx),
+                    text_field(text, ctx, x, y),
+                ))
+            }
+            Widget::Text { x, y, ref text } => Box::new(text_field(text.clone(), ctx, x, y)),
+            Widget::WaterMark { x, y, w, h } => {
+                let r = Rect::resolve(screen, x, y, w, h);
+
+                Box::new(WaterMark::new(r.x, r.y, r.w, r.h, ctx.tide_model))
+            }
+            Widget::Graph {
+                x,
+                y,
+                w,
+                h,
+                offset_hours: 0,
+            } => {
+                let r = Rect::resolve(screen, x, y, w, h);
+
+                Box::new(GraphCanvas::new(
+                    r.x,
+                    r.y,
+                    r.w,
+                    r.h,
+                    *ctx.tide_window,
+                    ctx.font,
+                    ctx.local_time.offset().fix(),
+                ))
+            }
+            Widget::Graph {
+                x,
+                y,
+                w,
+                h,
+                offset_hours,
+            } => {
+                let r = Rect::resolve(screen, x, y, w, h);
+                let offset = Duration::hours(offset_hours);
+                let shifted = ctx.local_time + offset;
+                let (window, _) = ctx.tide_model.get_window(shifted, r.w);
+                let graph =
+                    GraphCanvas::new(r.x, r.y, r.w, r.h, window, ctx.font, shifted.offset().fix());
+
+                Box::new(TimeShift::new(offset, graph))
+            }
+            Widget::Extremes { x, y, rows } => {
+                let utc_now = tides::local_to_utc(ctx.local_time);
+                let upcoming = ctx.tide_model.upcoming_extremes(utc_now);
+                //The first one drops off the list once it's passed
+                let changes = upcoming.first().map(|e| e.date() + Duration::seconds(1));
+                let lines = upcoming
+                    .iter()
+                    .take(rows)
+                    .map(|e| {
+                        let kind = match e.kind() {
+                            ExtremeKind::High => "HIGH",
+                            ExtremeKind::Low => "LOW",
+                        };
+                        let local_dt = e.date().with_timezone(&Local);
+
+                        format!("{} {} {:.1}m", kind, local_dt.format("%H:%M"), e.height())
+                    })
+                    .collect();
+
+                Box::new(ChangesAt::new(changes, text_list(lines, ctx, x, y)))
+            }
+            Widget::Statistics { x, y, hours } => {
+                let utc_now = tides::local_to_utc(ctx.local_time);
+                let lines = match ctx
+                    .tide_model
+                    .statistics(utc_now, utc_now + Duration::hours(hours))
+                {
+                    Some(stats) => vec![
+                        format!("HIGH {:.1}m", stats.high_water),
+                        format!("LOW {:.1}m", stats.low_water),
+                        format!("MEAN {:.1}m", stats.mean_water),
+                        format!("RANGE {:.1}m", stats.range),
+                    ],
+                    None => vec!["NO DATA".to_string()],
+                };
+
+                Box::new(ChangesAt::new(
+                    next_sample_change(ctx),
+                    text_list(lines, ctx, x, y),
+                ))
+            }
+        }
+    }
+}
+
+// High and low water, from the window now or the 24 hours from `offset_hours` ahead
+fn water_levels(ctx: &FrameContext, offset_hours: i64) -> Option<(f32, f32)> {
+    if offset_hours == 0 {
+        let mark = ctx.tide_window.water_mark();
+        return Some((mark.high_water, mark.low_water));
+    }
+
+    let from = tides::local_to_utc(ctx.local_time) + Duration::hours(offset_hours);
+    ctx.tide_model
+        .statistics(from, from + Duration::hours(24))
+        .map(|stats| (stats.high_water, stats.low_water))
+}
+
+fn level_text(level: Option<f32>) -> String {
+    match level {
+        Some(level) => format!("{:.1}m", level),
+        None => "?".to_string(),
+    }
+}
+
+fn next_sample_change(ctx: &FrameContext) -> Option<DateTime<Utc>> {
+    ctx.tide_model
+        .next_sample_change(tides::local_to_utc(ctx.local_time))
+}
+
+fn text_field<'a>(text: String, ctx: &FrameContext<'a>, x: i32, y: i32) -> TextField<'a> {
+    let x = resolve_axis(ctx.screen.0, x);
+    let y = resolve_axis(ctx.screen.1, y);
+
+    TextField::new(text, ctx.font, x, y)
+}
+
+fn text_list<'a>(lines: Vec<String>, ctx: &FrameContext<'a>, x: i32, y: i32) -> TextList<'a> {
+    let x = resolve_axis(ctx.screen.0, x);
+    let y = resolve_axis(ctx.screen.1, y);
+
+    TextList::new(lines, ctx.font, x, y, LINE_HEIGHT)
+}
+
+// Paints every widget of the page onto a blank frame
+pub fn compose(page: &Page, ctx: &FrameContext) -> MonoFrame {
+    let mut img: MonoFrame = MonoFrame::new(ctx.screen.0, ctx.screen.1);
+
+    let utc_now = tides::local_to_utc(ctx.local_time);
+
+    for painter in page.painters(ctx).iter() {
+        painter.paint(&mut img, utc_now);
+    }
+
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::snapshot::{
+        assert_snapshot, fixture_time, init_tz, with_fixture_ctx, with_fixture_screen,
+    };
+    use crate::tides::local_to_utc;
+
+    fn layout_with_seconds(seconds: &[u32]) -> Layout {
+        Layout {
+            rotation: Rotation::default(),
+            pages: seconds
+                .iter()
+                .map(|s| Page {
+                    name: String::new(),
+                    seconds: *s,
+                    widgets: vec![],
+                })
+                .collect(),
+            night: Page::night(),
+        }
+    }
+
+    fn paint_page(page: &Page, screen: (u32, u32), time: &str) -> MonoFrame {
+        with_fixture_screen(screen, time, |ctx| {
+            let mut img = MonoFrame::new(screen.0, screen.1);
+            for painter in page.painters(ctx).iter() {
+                painter.paint(&mut img, local_to_utc(ctx.local_time));
+            }
+
+            img
+        })
+    }
+
+    #[test]
+    fn test_shipped_layout_starts_with_default_page() {
+        let layout = Layout::load("resources/Layout.toml").unwrap();
+
+        assert_eq!(layout.pages[0].widgets, Page::default().widgets);
+    }
+
+    #[test]
+    fn test_shipped_pages_snapshot() {
+        init_tz();
+        let layout = Layout::load("resources/Layout.toml").unwrap();
+
+        for page in layout.pages.iter().chain(std::iter::once(&layout.night)) {
+            let img = paint_page(page, (128, 32), "2020-10-02T19:00:00Z");
+
+            assert_snapshot(&format!("page_{}", page.name), &img);
+        }
+    }
+
+    #[test]
+    fn test_default_page_adapts_to_screen_size() {
+        init_tz();
+
+        for screen in &[(128, 64), (96, 16)] {
+            let img = paint_page(&Page::default(), *screen, "2020-10-02T19:00:00Z");
+
+            assert_snapshot(&format!("page_graph_{}x{}", screen.0, screen.1), &img);
+        }
+    }
+
+    #[test]
+    fn test_graph_width() {
+        let layout = Layout::default();
+
+        assert_eq!(layout.graph_width((128, 32)), 107);
+        assert_eq!(layout.graph_width((256, 64)), 235);
+        assert_eq!(layout.graph_width((16, 16)), 0);
+    }
+
+    #[test]
+    fn test_samples_needed() {
+        let mut layout = Layout::default();
+        let hour = Some(Duration::hours(1));
+
+        assert_eq!(layout.samples_needed((128, 32), hour), 107);
+
+        //Tomorrow's graph needs the 24 samples before it as well
+        layout.pages.push(Page {
+            name: String::new(),
+            seconds: 10,
+            widgets: vec![Widget::Graph {
+                x: 21,
+                y: 10,
+                w: None,
+                h: None,
+                offset_hours: 24,
+            }],
+        });
+        assert_eq!(layout.samples_needed((128, 32), hour), 107 + 24);
+        assert_eq!(
+            layout.samples_needed((128, 32), Some(Duration::seconds(1000))),
+            107 + 87
+        );
+        assert_eq!(layout.samples_needed((128, 32), None), 107);
+    }
+
+    #[test]
+    fn test_resolve_rect() {
+        let r = Rect::resolve((128, 64), -10, 8, None, Some(4));
+
+        assert_eq!((r.x, r.y, r.w, r.h), (118, 8, 10, 4));
+
+        let r = Rect::resolve((128, 64), 0, -200, Some(3), None);
+
+        assert_eq!((r.x, r.y, r.w, r.h), (0, 0, 3, 64));
+
+        //Clipped to the screen
+        let r = Rect::resolve((128, 64), 120, 200, Some(20), Some(4));
+
+        assert_eq!((r.x, r.y, r.w, r.h), (120, 200, 8, 0));
+    }
+
+    #[test]
+    fn test_widgets_on_the_edges() {
+        let page = Page {
+            name: "edges".to_string(),
+            seconds: 30,
+            widgets: vec![
+                Widget::Graph {
+                    x: 0,
+                    y: 0,
+                    w: None,
+                    h: None,
+                    offset_hours: 0,
+                },
+                Widget::WaterMark {
+                    x: 0,
+                    y: 0,
+                    w: Some(2),
+                    h: Some(1),
+                },
+                Widget::WaterMark {
+                    x: -1,
+                    y: -1,
+                    w: None,
+                    h: None,
+                },
+                Widget::Graph {
+                    x: 200,
+                    y: 200,
+                    w: None,
+                    h: None,
+                    offset_hours: 24,
+                },
+                Widget::Clock {
+                    x: -1,
+                    y: -1,
+                    blink: false,
+                },
+                Widget::Extremes {
+                    x: 127,
+                    y: 31,
+                    rows: 4,
+                },
+            ],
+        };
+
+        for &screen in &[(128, 32), (128, 64), (1, 1)] {
+            with_fixture_screen(screen, "2020-10-02T19:00:00Z", |ctx| {
+                let frame = compose(&page, ctx);
+                assert_eq!(frame.dimensions(), screen);
+                assert!(frame.lit_bounds().is_some());
+            });
+        }
+    }
+
+    #[test]
+    fn test_empty_widget_is_an_error() {
+        let path = std::env::temp_dir().join("tide-clock-empty-widget.toml");
+        std::fs::write(
+            &path,
+            "[[page]]\n[[page.widget]]\ntype = \"graph\"\nx = 0\ny = 0\nh = 0\n",
+        )
+        .unwrap();
+
+        assert!(Layout::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_page_index_at() {
+        let layout = layout_with_seconds(&[10, 5, 1]);
+        let at = |seconds: i64| {
+            let time = fixture_time("1970-01-01T00:00:00Z") + Duration::seconds(seconds);
+            layout.page_index_at(time.with_timezone(&Local))
+        };
+
+        assert_eq!(at(0), 0);
+        assert_eq!(at(9), 0);
+        assert_eq!(at(10), 1);
+        assert_eq!(at(14), 1);
+        assert_eq!(at(15), 2);
+        assert_eq!(at(16), 0);
+        assert_eq!(at(16 * 1000 + 12), 1);
+    }
+
+    #[test]
+    fn test_next_page_change() {
+        let layout = layout_with_seconds(&[10, 5, 1]);
+        let next = |seconds: i64| {
+            let time = fixture_time("1970-01-01T00:00:00Z") + Duration::seconds(seconds);
+            let next = layout.next_page_change(time.with_timezone(&Local)).unwrap();
+            (next - fixture_time("1970-01-01T00:00:00Z")).num_seconds()
+        };
+
+        assert_eq!(next(0), 10);
+        assert_eq!(next(9), 10);
+        assert_eq!(next(14), 15);
+        assert_eq!(next(15), 16);
+        assert_eq!(
+            layout_with_seconds(&[10]).next_page_change(Local::now()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_page_next_change() {
+        with_fixture_ctx("2020-10-02T19:00:00Z", |ctx| {
+            //The colon blinks every second
+            assert_eq!(
+                Page::default().next_change(ctx),
+                Some(fixture_time("2020-10-02T19:00:01Z"))
+            );
+            //Without a clock it's the graph moving along
+            let page = Page {
+                name: String::new(),
+                seconds: 10,
+                widgets: vec![Widget::Graph {
+                    x: 0,
+                    y: 0,
+                    w: None,
+                    h: None,
+                    offset_hours: 0,
+                }],
+            };
+            let next = page.next_change(ctx).unwrap();
+            assert!(next > fixture_time("2020-10-02T19:00:01Z"));
+        });
+    }
+
+    #[test]
+    fn test_water_levels_for_tomorrow() {
+        with_fixture_ctx("2020-10-02T19:00:00Z", |ctx| {
+            let mark = ctx.tide_window.water_mark();
+            assert_eq!(
+                water_levels(ctx, 0),
+                Some((mark.high_water, mark.low_water))
+            );
+
+            let tomorrow = fixture_time("2020-10-03T19:00:00Z");
+            let stats = ctx
+                .tide_model
+                .statistics(tomorrow, tomorrow + Duration::hours(24))
+                .unwrap();
+            assert_eq!(
+                water_levels(ctx, 24),
+                Some((stats.high_water, stats.low_water))
+            );
+            //Past the end of the data
+            assert_eq!(water_levels(ctx, 24 * 7), None);
+        });
+        assert_eq!(level_text(None), "?");
+    }
+
+    #[test]
+    fn test_layout_without_pages_is_an_error() {
+        let dir = std::env::temp_dir().join("tide-clock-empty-layout.toml");
+        std::fs::write(&dir, "[rotation]\ntransition = \"slide\"\n").unwrap();
+
+        assert!(Layout::load(dir.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_widgets() {
+        let layout: Layout = toml::from_str(
+            r#"
+            [rotation]
+            transition = "wipe"
+
+            [[page]]
+            name = "test"
+            seconds = 5
+
+            [[page.widget]]
+            type = "clock"
+            x = 1
+            y = 2
+            blink = false
+
+            [[page.widget]]
+            type = "text"
+            x = 3
+            y = 4
+            text = "HI"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(layout.rotation.transition, Transition::Wipe);
+        assert_eq!(layout.pages[0].seconds, 5);
+        assert_eq!(
+            layout.pages[0].widgets,
+            vec![
+                Widget::Clock {
+                    x: 1,
+                    y: 2,
+                    blink: false
+                },
+                Widget::Text {
+                    x: 3,
+                    y: 4,
+                    text: "HI".to_string()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_widget_is_an_error() {
+        let result = toml::from_str::<Layout>(
+            r#"
+            [[page]]
+            [[page.widget]]
+            type = "sundial"
+            x = 0
+            y = 0
+            "#,
+        );
+
+        assert!(result.is_err());
+    }
+}
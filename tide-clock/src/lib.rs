@@ -0,0 +1,40 @@
+// The tide clock as a library: the tide model and its API loader, the widgets that paint
+// the face and the devices that show it. The binary in main.rs just runs cli::run, other
+// displays can put the pieces together their own way
+pub mod app;
+pub mod burn_in;
+pub mod cli;
+pub mod display;
+pub mod font;
+pub mod framebuffer;
+pub mod layout;
+pub mod mirror;
+#[cfg(test)]
+mod mock_hal;
+pub mod night;
+//The panel drivers are built everywhere so they can be tested against a mock bus,
+//but only drive hardware with the ssd1305, ssd1306 or sh1106 features
+pub mod oled;
+pub mod paths;
+pub mod preview;
+pub mod schedule;
+pub mod settings;
+pub mod sh1106;
+//Golden images and fixtures, shared by the unit tests and the ones in tests/. Kept out of
+//the normal build, the tests in tests/ turn it on through test-support
+#[cfg(any(test, feature = "test-support"))]
+#[doc(hidden)]
+pub mod snapshot;
+pub mod ssd1305;
+pub mod ssd1306;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+pub mod ticker;
+pub mod tides;
+
+//The model and painters are in the no_std core, shared with the microcontroller build
+pub use tide_clock_core::{maths, mono_frame};
+
+pub use display::{Painter, RenderDevice};
+pub use mono_frame::MonoFrame;
+pub use tides::TideModel;
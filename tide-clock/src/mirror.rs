@@ -0,0 +1,351 @@
+use crate::display::{Backend, DisplayControl, HardwareScroll, RenderDevice};
+use crate::mono_frame::MonoFrame;
+use serde::{Deserialize, Serialize};
+use simple_error::SimpleError;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+// One [[display.mirror]] entry in Settings.toml, an extra output for the same frames
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MirrorSettings {
+    pub backend: Backend,
+    //At most one frame per so many seconds, 0 for every frame
+    #[serde(default)]
+    pub every_seconds: u64,
+}
+
+struct Child {
+    name: String,
+    device: Box<dyn RenderDevice>,
+    every: Duration,
+    last_render: Option<Instant>,
+    //The latest frame that came in before it was due, sent once it is
+    pending: Option<MonoFrame>,
+}
+
+impl Child {
+    fn due(&self, now: Instant) -> bool {
+        match self.last_render {
+            Some(last) => now.duration_since(last) >= self.every,
+            None => true,
+        }
+    }
+
+    // False when the device failed, after trying to reset it
+    fn send(&mut self, frame: &MonoFrame, now: Instant) -> bool {
+        match self.device.render(frame) {
+            Ok(()) => {
+                self.last_render = Some(now);
+                self.pending = None;
+                true
+            }
+            Err(e) => {
+                println!("Could not render to {}, resetting. Err {}", self.name, e);
+                if let Err(e) = self.device.reinit() {
+                    println!("Could not reset {}. Err {}", self.name, e);
+                }
+                false
+            }
+        }
+    }
+}
+
+// Sends every frame to several devices, e.g. the panel plus a BMP to check on it remotely.
+// A device failing doesn't hold up the others, it's reinitialised and gets the next frame
+pub struct MirrorDevice {
+    children: Vec<Child>,
+}
+
+impl MirrorDevice {
+    // The first device sets the size
+    pub fn new(name: &str, device: Box<dyn RenderDevice>) -> MirrorDevice {
+        let mut mirror = MirrorDevice { children: vec![] };
+        mirror.add(name, device, Duration::from_secs(0));
+        mirror
+    }
+
+    pub fn add(&mut self, name: &str, device: Box<dyn RenderDevice>, every: Duration) {
+        self.children.push(Child {
+            name: name.to_string(),
+            device,
+            every,
+            last_render: None,
+            pending: None,
+        });
+    }
+}
+
+impl RenderDevice for MirrorDevice {
+    // Devices that aren't due yet keep the frame for later, see flush. Only fails when every
+    // device that was due a frame failed
+    fn render(&mut self, buffer: &MonoFrame) -> Result<(), Box<dyn Error>> {
+        let now = Instant::now();
+        let (mut attempts, mut failures) = (0, 0);
+
+        for child in self.children.iter_mut() {
+            if !child.due(now) {
+                child.pending = Some(buffer.clone());
+                continue;
+            }
+
+            attempts += 1;
+            if !child.send(buffer, now) {
+                failures += 1;
+            }
+        }
+
+        match failures > 0 && failures == attempts {
+            true => Err(SimpleError::new("Every display failed to render").into()),
+            false => Ok(()),
+        }
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.children[0].device.size()
+    }
+
+    // Throttled devices get the last frame they missed once they're due, otherwise they'd
+    // show it late or not at all when the face doesn't change again for a while
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        let now = Instant::now();
+        for child in self.children.iter_mut().filter(|c| c.due(now)) {
+            if let Some(frame) = child.pending.take() {
+                child.send(&frame, now);
+            }
+        }
+        Ok(())
+    }
+
+    fn next_flush(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.children
+            .iter()
+            .filter(|c| c.pending.is_some())
+            .map(|c| match c.last_render {
+                Some(last) => (last + c.every).saturating_duration_since(now),
+                None => Duration::from_secs(0),
+            })
+            .min()
+    }
+
+    fn reinit(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut result = Ok(());
+        for child in &mut self.children {
+            child.last_render = None;
+            if let Err(e) = child.device.reinit() {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    fn controls(&mut self) -> Option<&mut dyn DisplayControl> {
+        Some(self)
+    }
+
+    // The main device scrolls when it can, the mirrors keep showing the frame it started from
+    fn scroller(&mut self) -> Option<&mut dyn HardwareScroll> {
+        self.children[0].device.scroller()
+    }
+}
+
+// Applied to every device that has controls, carrying on past failures so one broken
+// device doesn't leave the rest in a different state
+impl MirrorDevice {
+    fn each_control<F>(&mut self, mut f: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&mut dyn DisplayControl) -> Result<(), Box<dyn Error>>,
+    {
+        let mut result = Ok(());
+        for child in &mut self.children {
+            if let Some(controls) = child.device.controls() {
+                if let Err(e) = f(controls) {
+                    println!("Could not change {}. Err {}", child.name, e);
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl DisplayControl for MirrorDevice {
+    fn set_contrast(&mut self, contrast: u8) -> Result<(), Box<dyn Error>> {
+        self.each_control(|c| c.set_contrast(contrast))
+    }
+
+    fn set_inverted(&mut self, inverted: bool) -> Result<(), Box<dyn Error>> {
+        self.each_control(|c| c.set_inverted(inverted))
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), Box<dyn Error>> {
+        self.each_control(|c| c.set_display_on(on))
+    }
+
+    fn set_rotated(&mut self, rotated: bool) -> Result<(), Box<dyn Error>> {
+        self.each_control(|c| c.set_rotated(rotated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::EmulatedControls;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // What a test device was asked to do, shared with the test
+    #[derive(Default)]
+    struct Log {
+        frames: usize,
+        reinits: usize,
+        fail: bool,
+        controls: EmulatedControls,
+    }
+
+    struct TestDevice {
+        log: Rc<RefCell<Log>>,
+    }
+
+    impl RenderDevice for TestDevice {
+        fn render(&mut self, _buffer: &MonoFrame) -> Result<(), Box<dyn Error>> {
+            let mut log = self.log.borrow_mut();
+            match log.fail {
+                true => Err(SimpleError::new("Broken").into()),
+                false => {
+                    log.frames += 1;
+                    Ok(())
+                }
+            }
+        }
+
+        fn size(&self) -> (u32, u32) {
+            (128, 32)
+        }
+
+        fn reinit(&mut self) -> Result<(), Box<dyn Error>> {
+            self.log.borrow_mut().reinits += 1;
+            Ok(())
+        }
+
+        fn controls(&mut self) -> Option<&mut dyn DisplayControl> {
+            Some(self)
+        }
+    }
+
+    impl DisplayControl for TestDevice {
+        fn set_contrast(&mut self, contrast: u8) -> Result<(), Box<dyn Error>> {
+            self.log.borrow_mut().controls.set_contrast(contrast)
+        }
+
+        fn set_inverted(&mut self, inverted: bool) -> Result<(), Box<dyn Error>> {
+            self.log.borrow_mut().controls.set_inverted(inverted)
+        }
+
+        fn set_display_on(&mut self, on: bool) -> Result<(), Box<dyn Error>> {
+            self.log.borrow_mut().controls.set_display_on(on)
+        }
+
+        fn set_rotated(&mut self, rotated: bool) -> Result<(), Box<dyn Error>> {
+            self.log.borrow_mut().controls.set_rotated(rotated)
+        }
+    }
+
+    fn device() -> (Box<dyn RenderDevice>, Rc<RefCell<Log>>) {
+        let log = Rc::new(RefCell::new(Log::default()));
+        (Box::new(TestDevice { log: log.clone() }), log)
+    }
+
+    #[test]
+    fn test_frames_go_to_every_device() {
+        let (panel, panel_log) = device();
+        let (file, file_log) = device();
+        let mut mirror = MirrorDevice::new("panel", panel);
+        mirror.add("file", file, Duration::from_secs(0));
+
+        let frame = MonoFrame::new(128, 32);
+        mirror.render(&frame).unwrap();
+        mirror.render(&frame).unwrap();
+
+        assert_eq!(panel_log.borrow().frames, 2);
+        assert_eq!(file_log.borrow().frames, 2);
+        assert_eq!(mirror.size(), (128, 32));
+
+        mirror.controls().unwrap().set_inverted(true).unwrap();
+        assert!(panel_log.borrow().controls.inverted);
+        assert!(file_log.borrow().controls.inverted);
+    }
+
+    #[test]
+    fn test_throttled_device_skips_frames() {
+        let (panel, panel_log) = device();
+        let (file, file_log) = device();
+        let mut mirror = MirrorDevice::new("panel", panel);
+        mirror.add("file", file, Duration::from_secs(60));
+
+        let frame = MonoFrame::new(128, 32);
+        for _ in 0..3 {
+            mirror.render(&frame).unwrap();
+        }
+
+        assert_eq!(panel_log.borrow().frames, 3);
+        assert_eq!(file_log.borrow().frames, 1);
+
+        //A reset of the whole mirror redraws everything
+        mirror.reinit().unwrap();
+        mirror.render(&frame).unwrap();
+        assert_eq!(file_log.borrow().frames, 2);
+    }
+
+    #[test]
+    fn test_throttled_device_gets_the_last_frame_later() {
+        let (panel, _) = device();
+        let (file, file_log) = device();
+        let mut mirror = MirrorDevice::new("panel", panel);
+        mirror.add("file", file, Duration::from_millis(20));
+
+        let frame = MonoFrame::new(128, 32);
+        mirror.render(&frame).unwrap();
+        assert_eq!(mirror.next_flush(), None);
+
+        //Comes in too soon, so it's held back until the file is due
+        mirror.render(&frame).unwrap();
+        assert_eq!(file_log.borrow().frames, 1);
+        let wait = mirror.next_flush().unwrap();
+        assert!(wait <= Duration::from_millis(20));
+
+        mirror.flush().unwrap();
+        assert_eq!(file_log.borrow().frames, 1);
+
+        std::thread::sleep(wait);
+        mirror.flush().unwrap();
+        assert_eq!(file_log.borrow().frames, 2);
+        assert_eq!(mirror.next_flush(), None);
+    }
+
+    #[test]
+    fn test_failing_device_is_isolated() {
+        let (panel, panel_log) = device();
+        let (file, file_log) = device();
+        let mut mirror = MirrorDevice::new("panel", panel);
+        mirror.add("file", file, Duration::from_secs(60));
+        let frame = MonoFrame::new(128, 32);
+
+        //The file still gets the frame and the panel is reset
+        panel_log.borrow_mut().fail = true;
+        mirror.render(&frame).unwrap();
+        assert_eq!(file_log.borrow().frames, 1);
+        assert_eq!(panel_log.borrow().reinits, 1);
+
+        //Once it's back it gets the next frame
+        panel_log.borrow_mut().fail = false;
+        mirror.render(&frame).unwrap();
+        assert_eq!(panel_log.borrow().frames, 1);
+
+        //When nothing took the frame it's an error
+        panel_log.borrow_mut().fail = true;
+        file_log.borrow_mut().fail = true;
+        mirror.reinit().unwrap();
+        assert!(mirror.render(&frame).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use embedded_hal::blocking::{i2c, spi};
+use embedded_hal::digital::v2::OutputPin;
+use std::cell::{Cell, RefCell};
+use std::convert::Infallible;
+use std::rc::Rc;
+
+// What a panel driver did to the bus, in order
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Command(u8),
+    Data(Vec<u8>),
+    Reset(bool),
+    //A whole I2C write, address and bytes including the control byte
+    I2c(u8, Vec<u8>),
+}
+
+// In-memory stand-in for the SPI or I2C bus and the DC and reset pins of a panel. SPI writes
+// with DC low are recorded as one command per byte, writes with DC high as a block of data.
+// I2C writes are recorded as they are, so the framing can be checked.
+// The bus can be told to fail writes, or just the next one, to exercise error handling
+#[derive(Clone, Default)]
+pub struct MockBus {
+    events: Rc<RefCell<Vec<Event>>>,
+    dc_high: Rc<Cell<bool>>,
+    fail_writes: Rc<Cell<bool>>,
+    fail_next: Rc<Cell<bool>>,
+}
+
+// Returned by every write while the bus is set to fail
+#[derive(Debug)]
+pub struct MockError;
+
+impl MockBus {
+    pub fn spi(&self) -> MockSpi {
+        MockSpi { bus: self.clone() }
+    }
+
+    pub fn i2c(&self) -> MockI2c {
+        MockI2c { bus: self.clone() }
+    }
+
+    pub fn dc(&self) -> MockPin {
+        MockPin {
+            bus: self.clone(),
+            role: PinRole::DataCommand,
+        }
+    }
+
+    pub fn rst(&self) -> MockPin {
+        MockPin {
+            bus: self.clone(),
+            role: PinRole::Reset,
+        }
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.events.borrow().clone()
+    }
+
+    pub fn commands(&self) -> Vec<u8> {
+        self.events
+            .borrow()
+            .iter()
+            .filter_map(|e| match e {
+                Event::Command(c) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // All data bytes concatenated
+    pub fn data(&self) -> Vec<u8> {
+        self.events
+            .borrow()
+            .iter()
+            .filter_map(|e| match e {
+                Event::Data(d) => Some(d.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn fail_writes(&self, fail: bool) {
+        self.fail_writes.set(fail);
+    }
+
+    //A glitch, the write after it goes through again
+    pub fn fail_next_write(&self) {
+        self.fail_next.set(true);
+    }
+
+    fn write_fails(&self) -> bool {
+        self.fail_writes.get() || self.fail_next.replace(false)
+    }
+
+    pub fn clear(&self) {
+        self.events.borrow_mut().clear();
+    }
+}
+
+pub struct MockSpi {
+    bus: MockBus,
+}
+
+impl spi::Write<u8> for MockSpi {
+    type Error = MockError;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), MockError> {
+        if self.bus.write_fails() {
+            return Err(MockError);
+        }
+
+        let mut events = self.bus.events.borrow_mut();
+        if self.bus.dc_high.get() {
+            events.push(Event::Data(words.to_vec()));
+        } else {
+            events.extend(words.iter().map(|&c| Event::Command(c)));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct MockI2c {
+    bus: MockBus,
+}
+
+impl i2c::Write for MockI2c {
+    type Error = MockError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), MockError> {
+        if self.bus.write_fails() {
+            return Err(MockError);
+        }
+
+        self.bus
+            .events
+            .borrow_mut()
+            .push(Event::I2c(address, bytes.to_vec()));
+        Ok(())
+    }
+}
+
+enum PinRole {
+    DataCommand,
+    Reset,
+}
+
+pub struct MockPin {
+    bus: MockBus,
+    role: PinRole,
+}
+
+impl MockPin {
+    fn set(&mut self, high: bool) {
+        match self.role {
+            PinRole::DataCommand => self.bus.dc_high.set(high),
+            PinRole::Reset => self.bus.events.borrow_mut().push(Event::Reset(high)),
+        }
+    }
+}
+
+impl OutputPin for MockPin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.set(true);
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use crate::display::{RenderDevice, DEFAULT_CONTRAST};
+use crate::settings::DisplaySettings;
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::error::Error;
+
+const TIME_FORMAT: &str = "%H:%M";
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Copy, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum NightMode {
+    //Same face, lower contrast
+    Dim,
+    //Lower contrast and the night page from Layout.toml instead of the rotation
+    Minimal,
+    //Panel off
+    Sleep,
+}
+
+// The [night] section of Settings.toml. Times are local, "HH:MM", and the window may run
+// past midnight
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct NightSettings {
+    #[serde(
+        deserialize_with = "deserialize_time",
+        serialize_with = "serialize_time"
+    )]
+    pub start: NaiveTime,
+    #[serde(
+        deserialize_with = "deserialize_time",
+        serialize_with = "serialize_time"
+    )]
+    pub end: NaiveTime,
+    pub mode: NightMode,
+    #[serde(default = "default_night_contrast")]
+    pub contrast: u8,
+}
+
+fn default_night_contrast() -> u8 {
+    0x08
+}
+
+fn deserialize_time<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&s, TIME_FORMAT).map_err(de::Error::custom)
+}
+
+fn serialize_time<S>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&time.format(TIME_FORMAT).to_string())
+}
+
+impl NightSettings {
+    // None during the day. Start is inclusive and end exclusive, equal times mean never
+    pub fn mode_at(&self, local_time: DateTime<Local>) -> Option<NightMode> {
+        let time = local_time.time();
+
+        let is_night = match self.start <= self.end {
+            true => time >= self.start && time < self.end,
+            false => time >= self.start || time < self.end,
+        };
+
+        match is_night {
+            true => Some(self.mode),
+            false => None,
+        }
+    }
+
+    // The next start or end after now. None only when a DST jump skips over both
+    pub fn next_change(&self, local_time: DateTime<Local>) -> Option<DateTime<Utc>> {
+        let today = local_time.naive_local().date();
+
+        [self.start, self.end]
+            .iter()
+            .flat_map(|time| {
+                vec![
+                    today.and_time(*time),
+                    (today + Duration::days(1)).and_time(*time),
+                ]
+            })
+            .filter_map(|naive| Local.from_local_datetime(&naive).earliest())
+            .filter(|at| *at > local_time)
+            .map(|at| at.with_timezone(&Utc))
+            .min()
+    }
+}
+
+// Switches the device between day and night. Devices without controls keep the day face,
+// only the minimal page applies to them
+pub fn apply(
+    render_device: &mut dyn RenderDevice,
+    mode: Option<NightMode>,
+    night: &NightSettings,
+    display: &DisplaySettings,
+) -> Result<(), Box<dyn Error>> {
+    let controls = match render_device.controls() {
+        Some(controls) => controls,
+        None => return Ok(()),
+    };
+
+    match mode {
+        None => {
+            controls.set_contrast(display.contrast.unwrap_or(DEFAULT_CONTRAST))?;
+            controls.set_display_on(true)?;
+        }
+        Some(NightMode::Dim) | Some(NightMode::Minimal) => {
+            controls.set_contrast(night.contrast)?;
+            controls.set_display_on(true)?;
+        }
+        Some(NightMode::Sleep) => controls.set_display_on(false)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{fixture_time, init_tz};
+
+    fn night(start: &str, end: &str) -> NightSettings {
+        toml::from_str(&format!(
+            "start = \"{}\"\nend = \"{}\"\nmode = \"dim\"",
+            start, end
+        ))
+        .unwrap()
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        init_tz();
+        fixture_time(&format!("2020-10-02T{:02}:{:02}:00Z", hour, minute)).with_timezone(&Local)
+    }
+
+    #[test]
+    fn test_mode_at_past_midnight() {
+        let night = night("22:30", "07:00");
+
+        assert_eq!(night.mode_at(at(22, 29)), None);
+        assert_eq!(night.mode_at(at(22, 30)), Some(NightMode::Dim));
+        assert_eq!(night.mode_at(at(3, 0)), Some(NightMode::Dim));
+        assert_eq!(night.mode_at(at(6, 59)), Some(NightMode::Dim));
+        assert_eq!(night.mode_at(at(7, 0)), None);
+        assert_eq!(night.contrast, 0x08);
+    }
+
+    #[test]
+    fn test_mode_at_same_day() {
+        let night = night("01:00", "05:00");
+
+        assert_eq!(night.mode_at(at(0, 59)), None);
+        assert_eq!(night.mode_at(at(1, 0)), Some(NightMode::Dim));
+        assert_eq!(night.mode_at(at(5, 0)), None);
+
+        assert_eq!(self::night("01:00", "01:00").mode_at(at(1, 0)), None);
+    }
+
+    #[test]
+    fn test_next_change() {
+        let night = night("22:30", "07:00");
+        let next = |hour, minute| night.next_change(at(hour, minute)).unwrap();
+
+        assert_eq!(next(12, 0), fixture_time("2020-10-02T22:30:00Z"));
+        assert_eq!(next(22, 30), fixture_time("2020-10-03T07:00:00Z"));
+        assert_eq!(next(3, 0), fixture_time("2020-10-02T07:00:00Z"));
+    }
+
+    #[test]
+    fn test_bad_time_is_rejected() {
+        let parsed: Result<NightSettings, _> =
+            toml::from_str("start = \"10pm\"\nend = \"07:00\"\nmode = \"sleep\"");
+
+        assert!(parsed.is_err());
+    }
+
+    // The BMP emulator dims and blanks the way the panel does
+    #[test]
+    #[cfg(feature = "image-writer")]
+    fn test_image_writer_emulates_night() {
+        use crate::display::ImageWriter;
+        use crate::mono_frame::MonoFrame;
+
+        let path = std::env::temp_dir().join(format!("night-{}.bmp", std::process::id()));
+        let mut writer = ImageWriter::with_path(4, 4, path.clone());
+        let display = DisplaySettings::default();
+        let mut frame = MonoFrame::new(4, 4);
+        frame.set_pixel(1, 1, true);
+
+        let lit_level = |writer: &mut ImageWriter, mode| {
+            apply(writer, mode, &night("22:00", "07:00"), &display).unwrap();
+            writer.render(&frame).unwrap();
+            image::open(&path)
+                .unwrap()
+                .as_rgb8()
+                .unwrap()
+                .get_pixel(1, 1)[0]
+        };
+
+        assert_eq!(lit_level(&mut writer, None), 255);
+        assert_eq!(lit_level(&mut writer, Some(NightMode::Dim)), 75);
+        assert_eq!(lit_level(&mut writer, Some(NightMode::Sleep)), 0);
+        assert_eq!(lit_level(&mut writer, None), 255);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
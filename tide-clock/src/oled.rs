@@ -0,0 +1,902 @@
+use crate::mono_frame::MonoFrame;
+use embedded_hal::blocking::{i2c, spi};
+use embedded_hal::digital::v2::OutputPin;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::time::Duration;
+use std::{error::Error, fmt, thread};
+
+// Why talking to a panel failed. The underlying HAL errors differ per platform, so they are
+// kept as their debug output
+#[derive(Debug, PartialEq)]
+pub enum DriverError {
+    //Opening the bus or GPIO pins failed, e.g. SPI isn't enabled and there's no /dev/spidev0.0
+    Setup(String),
+    //A write on the bus failed
+    Bus(String),
+    //Setting the data/command or reset pin failed
+    Pin(String),
+    //The chip doesn't have the feature, e.g. hardware scroll on the SH1106
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DriverError::Setup(e) => write!(f, "Could not open the display: {}", e),
+            DriverError::Bus(e) => write!(f, "Could not write to the display: {}", e),
+            DriverError::Pin(e) => write!(f, "Could not set a display pin: {}", e),
+            DriverError::Unsupported(what) => write!(f, "The display can't do {}", what),
+        }
+    }
+}
+
+impl Error for DriverError {}
+
+// Where the controller sends bytes. Commands and display data go down the same wire,
+// how they are told apart is up to the transport: the DC pin on SPI, a control byte on I2C
+pub trait Transport {
+    fn command(&mut self, cmd: u8) -> Result<(), DriverError>;
+    fn data(&mut self, data: &[u8]) -> Result<(), DriverError>;
+}
+
+// So the transport can be picked from the settings at runtime
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn command(&mut self, cmd: u8) -> Result<(), DriverError> {
+        (**self).command(cmd)
+    }
+
+    fn data(&mut self, data: &[u8]) -> Result<(), DriverError> {
+        (**self).data(data)
+    }
+}
+
+// Columns [start, end) of a page that differ from what the panel is currently showing
+#[derive(Debug, PartialEq)]
+pub struct DirtySpan {
+    pub page: u32,
+    pub start: u32,
+    pub end: u32,
+}
+
+// Local copy of the display RAM of an SSD13xx style panel. Keeps the last buffer that was
+// transmitted, so a flush only sends the pages and columns that changed since then
+pub struct PageBuffer {
+    width: u32,
+    height: u32,
+    //First visible column in display RAM, e.g. the SSD1305 has 132 columns for a 128 pixel panel
+    column_offset: u8,
+    buffer: Vec<u8>,
+    sent: Option<Vec<u8>>,
+    //Size of the last frame that didn't fit, so the mismatch is only reported once
+    mismatched: Option<(u32, u32)>,
+}
+
+impl PageBuffer {
+    pub fn new(width: u32, height: u32, column_offset: u8) -> PageBuffer {
+        //Pages are 8 rows high, round partial pages up
+        let pages = height.div_ceil(8);
+
+        PageBuffer {
+            width,
+            height,
+            column_offset,
+            buffer: vec![0x00; (width * pages) as usize],
+            sent: None,
+            mismatched: None,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: u8) {
+        if x >= self.width || y >= self.height {
+            println!("SetColor: Pixel out of bounds x:{} y:{}", x, y);
+            return;
+        }
+
+        //The display is split into strips of 8 rows (e.g. 4 strips of 128x8 on a 128x32 panel),
+        //where each column of 8 is encoded into a single value
+        let index = (x + (y / 8) * self.width) as usize;
+
+        if color > 0 {
+            self.buffer[index] |= 1 << (y % 8);
+        } else {
+            self.buffer[index] &= !(1 << (y % 8));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for i in 0..self.buffer.len() {
+            self.buffer[i] = 0;
+        }
+    }
+
+    //MonoFrame shares the panel's page layout, so a frame of the right size is a straight copy
+    pub fn load_frame(&mut self, frame: &MonoFrame) {
+        if frame.dimensions() == (self.width, self.height) {
+            self.buffer.copy_from_slice(frame.data());
+            return;
+        }
+
+        if self.mismatched != Some(frame.dimensions()) {
+            println!(
+                "Frame is {}x{} but the panel is {}x{}, clipping it",
+                frame.width(),
+                frame.height(),
+                self.width,
+                self.height
+            );
+            self.mismatched = Some(frame.dimensions());
+        }
+
+        self.clear();
+        for y in 0..frame.height().min(self.height) {
+            for x in 0..frame.width().min(self.width) {
+                let c = frame.get_pixel(x, y) as u8;
+                self.set_pixel(x, y, c);
+            }
+        }
+    }
+
+    // The panel reads the RAM from a different column, everything has to be sent again
+    pub fn set_column_offset(&mut self, column_offset: u8) {
+        self.column_offset = column_offset;
+        self.invalidate();
+    }
+
+    // Forget what the panel is showing, e.g. after a reset, so the next flush sends everything
+    pub fn invalidate(&mut self) {
+        self.sent = None;
+    }
+
+    // One span per page from the first to the last changed column. Resending a few unchanged
+    // bytes in between is cheaper than the three commands it takes to move the cursor
+    pub fn dirty_spans(&self) -> Vec<DirtySpan> {
+        let pages = self.height.div_ceil(8);
+        let width = self.width as usize;
+        let mut spans = vec![];
+
+        for page in 0..pages {
+            let start_index = page as usize * width;
+            let current = &self.buffer[start_index..start_index + width];
+
+            let (start, end) = match &self.sent {
+                None => (0, width),
+                Some(sent) => {
+                    let previous = &sent[start_index..start_index + width];
+                    let changed = |i: &usize| current[*i] != previous[*i];
+
+                    match (0..width).find(changed) {
+                        Some(first) => {
+                            let last = (0..width).rev().find(changed).unwrap_or(first);
+                            (first, last + 1)
+                        }
+                        None => continue,
+                    }
+                }
+            };
+
+            spans.push(DirtySpan {
+                page,
+                start: start as u32,
+                end: end as u32,
+            });
+        }
+
+        spans
+    }
+
+    // Page addressing mode: position the cursor with the page and column address commands,
+    // then stream the column bytes. Returns the number of data bytes sent
+    pub fn flush(&mut self, out: &mut dyn Transport) -> Result<usize, DriverError> {
+        let mut sent_bytes = 0;
+
+        for span in self.dirty_spans() {
+            match self.write_span(out, &span) {
+                Ok(bytes) => sent_bytes += bytes,
+                Err(e) => {
+                    //No telling how much made it to the panel
+                    self.invalidate();
+                    return Err(e);
+                }
+            }
+        }
+
+        self.sent = Some(self.buffer.clone());
+
+        Ok(sent_bytes)
+    }
+
+    fn write_span(&self, out: &mut dyn Transport, span: &DirtySpan) -> Result<usize, DriverError> {
+        let column = span.start + self.column_offset as u32;
+        out.command(0xB0 + span.page as u8)?; //Set page address
+        out.command((column & 0x0F) as u8)?; //Set low column address
+        out.command(0x10 | (column >> 4) as u8)?; //Set high column address
+
+        let start_index = (span.page * self.width + span.start) as usize;
+        let end_index = (span.page * self.width + span.end) as usize;
+        out.data(&self.buffer[start_index..end_index])?;
+
+        Ok(end_index - start_index)
+    }
+}
+
+// What sets the SSD1305, SSD1306 and SH1106 apart. The rest of the command set, the page
+// layout of display RAM and page addressing (the only mode the SH1106 has) are shared
+pub trait Chip {
+    // Everything sent after a reset, for a panel `height` rows high. Has to apply the
+    // settings in `state` and leave the chip in page addressing mode
+    fn init_sequence(&self, height: u32, state: &PanelState) -> Vec<u8>;
+
+    // First visible column in display RAM, which can change with the segment remap
+    fn column_offset(&self, rotated: bool) -> u8;
+
+    // Continuous horizontal scroll of pages 0 to `end_page`, `direction` is 0x26 or 0x27.
+    // None for chips without hardware scroll
+    fn scroll_setup(&self, _direction: u8, _end_page: u8) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+//Time between scroll steps, in frames. 0b100 is 3 frames on both the SSD1305 and SSD1306
+pub const SCROLL_INTERVAL: u8 = 0b100;
+
+// Settings the controller has been told to change since init. Kept so init can restore
+// them after a reset
+#[derive(Debug, PartialEq, Clone)]
+pub struct PanelState {
+    pub contrast: u8,
+    pub inverted: bool,
+    pub on: bool,
+    pub rotated: bool,
+}
+
+impl Default for PanelState {
+    fn default() -> PanelState {
+        PanelState {
+            contrast: 0x80,
+            inverted: false,
+            on: true,
+            rotated: false,
+        }
+    }
+}
+
+impl PanelState {
+    pub fn segment_remap(&self) -> u8 {
+        match self.rotated {
+            true => 0xA0,  //Column 0 is SEG0
+            false => 0xA1, //Last column is SEG0
+        }
+    }
+
+    pub fn com_scan_direction(&self) -> u8 {
+        match self.rotated {
+            true => 0xC0,  //Scan from COM0
+            false => 0xC8, //Scan from COM[N-1]
+        }
+    }
+
+    pub fn display_mode(&self) -> u8 {
+        match self.inverted {
+            true => 0xA7,
+            false => 0xA6,
+        }
+    }
+
+    pub fn display_power(&self) -> u8 {
+        match self.on {
+            true => 0xAF,
+            false => 0xAE,
+        }
+    }
+
+    // Scroll setup command that moves the picture right to left. The scroll commands go by
+    // segment, which the remap mirrors for the unrotated panel
+    pub fn scroll_left(&self) -> u8 {
+        match self.rotated {
+            true => 0x27,
+            false => 0x26,
+        }
+    }
+}
+
+// How the panel is wired up, from the [display.transport] table of the settings
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum TransportSettings {
+    Spi {
+        //SPI0 with chip select 0 is /dev/spidev0.0
+        #[serde(default)]
+        bus: u8,
+        #[serde(default)]
+        slave_select: u8,
+        #[serde(default = "default_speed_hz")]
+        speed_hz: u32,
+        //BCM pin numbers
+        #[serde(default = "default_dc_pin")]
+        dc_pin: u8,
+        #[serde(default = "default_spi_reset_pin")]
+        reset_pin: Option<u8>,
+    },
+    I2c {
+        //I2C1 is /dev/i2c-1, the one on the header
+        #[serde(default = "default_i2c_bus")]
+        bus: u8,
+        #[serde(default = "default_i2c_address")]
+        address: u8,
+        //Most I2C modules reset themselves on power up and don't break out the pin
+        #[serde(default)]
+        reset_pin: Option<u8>,
+    },
+}
+
+// The Waveshare HATs: SPI0 at 8 MHz with DC on BCM 24 and reset on BCM 25
+impl Default for TransportSettings {
+    fn default() -> TransportSettings {
+        TransportSettings::Spi {
+            bus: 0,
+            slave_select: 0,
+            speed_hz: default_speed_hz(),
+            dc_pin: default_dc_pin(),
+            reset_pin: default_spi_reset_pin(),
+        }
+    }
+}
+
+fn default_speed_hz() -> u32 {
+    8_000_000
+}
+
+fn default_dc_pin() -> u8 {
+    24
+}
+
+fn default_spi_reset_pin() -> Option<u8> {
+    Some(25)
+}
+
+fn default_i2c_bus() -> u8 {
+    1
+}
+
+//0x3D when the address jumper on the module is moved
+fn default_i2c_address() -> u8 {
+    0x3C
+}
+
+// The SPI bus plus the data/command pin, which tells the controller how to read the bytes
+pub struct SpiTransport<SPI, DC> {
+    spi: SPI,
+    dc: DC,
+}
+
+impl<SPI, DC> SpiTransport<SPI, DC> {
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        SpiTransport { spi, dc }
+    }
+}
+
+impl<SPI, DC> Transport for SpiTransport<SPI, DC>
+where
+    SPI: spi::Write<u8>,
+    SPI::Error: Debug,
+    DC: OutputPin,
+    DC::Error: Debug,
+{
+    fn command(&mut self, cmd: u8) -> Result<(), DriverError> {
+        self.dc.set_low().map_err(pin_error)?;
+        self.spi.write(&[cmd]).map_err(bus_error)
+    }
+
+    fn data(&mut self, data: &[u8]) -> Result<(), DriverError> {
+        self.dc.set_high().map_err(pin_error)?;
+        self.spi.write(data).map_err(bus_error)
+    }
+}
+
+// I2C has no DC pin, every write starts with a control byte saying what follows
+const I2C_COMMAND: u8 = 0x00;
+const I2C_DATA: u8 = 0x40;
+
+pub struct I2cTransport<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> I2cTransport<I2C> {
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        I2cTransport { i2c, address }
+    }
+}
+
+impl<I2C> Transport for I2cTransport<I2C>
+where
+    I2C: i2c::Write,
+    I2C::Error: Debug,
+{
+    fn command(&mut self, cmd: u8) -> Result<(), DriverError> {
+        self.i2c
+            .write(self.address, &[I2C_COMMAND, cmd])
+            .map_err(bus_error)
+    }
+
+    fn data(&mut self, data: &[u8]) -> Result<(), DriverError> {
+        let mut bytes = Vec::with_capacity(data.len() + 1);
+        bytes.push(I2C_DATA);
+        bytes.extend_from_slice(data);
+        self.i2c.write(self.address, &bytes).map_err(bus_error)
+    }
+}
+
+// Driver for the whole family, the chip decides the init sequence and column offsets.
+// Generic over the transport and the embedded-hal pin traits, so the driver runs against
+// rppal on the Pi and against a recording mock everywhere else
+pub struct OledController<C, T, RST> {
+    chip: C,
+    bus: T,
+    //Not every module breaks out the reset line
+    rst: Option<RST>,
+
+    frame: PageBuffer,
+    state: PanelState,
+}
+
+impl<C, T, RST> OledController<C, T, RST>
+where
+    C: Chip,
+    T: Transport,
+    RST: OutputPin,
+    RST::Error: Debug,
+{
+    // Doesn't talk to the panel yet, call init for that
+    pub fn new(chip: C, bus: T, rst: Option<RST>, width: usize, height: usize) -> Self {
+        let state = PanelState::default();
+        let column_offset = chip.column_offset(state.rotated);
+
+        OledController {
+            chip,
+            bus,
+            rst,
+            frame: PageBuffer::new(width as u32, height as u32, column_offset),
+            state,
+        }
+    }
+
+    // Resets the panel, sends the configuration and lights the four corners. Safe to call
+    // again to recover a panel that stopped responding
+    pub fn init(&mut self) -> Result<(), DriverError> {
+        self.reset()?;
+
+        for cmd in self.chip.init_sequence(self.frame.height(), &self.state) {
+            self.command(cmd)?;
+        }
+
+        let (width, height) = (self.width(), self.height());
+        self.clear();
+        self.set_pixel(0, 0, 1);
+        self.set_pixel(width - 1, 0, 1);
+        self.set_pixel(0, height - 1, 1);
+        self.set_pixel(width - 1, height - 1, 1);
+
+        self.display()
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        //Display RAM content is undefined after a reset
+        self.frame.invalidate();
+
+        //Without the pin the init sequence still starts from a known state, apart from RAM
+        let rst = match &mut self.rst {
+            Some(rst) => rst,
+            None => return Ok(()),
+        };
+
+        rst.set_high().map_err(pin_error)?;
+        thread::sleep(Duration::from_millis(10));
+        rst.set_low().map_err(pin_error)?;
+        thread::sleep(Duration::from_millis(10));
+        rst.set_high().map_err(pin_error)
+    }
+
+    fn command(&mut self, cmd: u8) -> Result<(), DriverError> {
+        self.bus.command(cmd)
+    }
+
+    // Higher is brighter, and wears the panel faster
+    pub fn set_contrast(&mut self, contrast: u8) -> Result<(), DriverError> {
+        self.state.contrast = contrast;
+        self.command(0x81)?; //Set contrast control
+        self.command(contrast)
+    }
+
+    // Lit pixels show dark and the background lit
+    pub fn set_inverted(&mut self, inverted: bool) -> Result<(), DriverError> {
+        self.state.inverted = inverted;
+        self.command(self.state.display_mode())
+    }
+
+    // Sleep mode. The panel keeps its RAM, so switching it back on shows the last frame
+    pub fn set_display_on(&mut self, on: bool) -> Result<(), DriverError> {
+        self.state.on = on;
+        self.command(self.state.display_power())
+    }
+
+    // Turns the image 180 degrees, for a panel mounted upside down. The segment remap only
+    // applies to data written afterwards, so the whole frame is sent again
+    pub fn set_rotated(&mut self, rotated: bool) -> Result<(), DriverError> {
+        self.state.rotated = rotated;
+        self.command(self.state.segment_remap())?;
+        self.command(self.state.com_scan_direction())?;
+
+        self.frame
+            .set_column_offset(self.chip.column_offset(rotated));
+        self.display()
+    }
+
+    pub fn can_scroll(&self) -> bool {
+        self.chip.scroll_setup(0x26, 0).is_some()
+    }
+
+    // Leaves the panel moving the frame it shows right to left on its own, wrapping around,
+    // until stop_scroll. Nothing is sent over the bus while it scrolls
+    pub fn start_scroll(&mut self) -> Result<(), DriverError> {
+        let end_page = self.frame.height().div_ceil(8) - 1;
+        let setup = self
+            .chip
+            .scroll_setup(self.state.scroll_left(), end_page as u8)
+            .ok_or(DriverError::Unsupported("hardware scroll"))?;
+
+        //The setup is ignored while a scroll is running
+        self.command(0x2E)?;
+        for cmd in setup {
+            self.command(cmd)?;
+        }
+        self.command(0x2F) //Activate scroll
+    }
+
+    // The chip leaves display RAM where the scroll got to, so the whole frame is sent again
+    pub fn stop_scroll(&mut self) -> Result<(), DriverError> {
+        self.command(0x2E)?; //Deactivate scroll
+        self.frame.invalidate();
+        self.display()
+    }
+
+    //Only the parts of the buffer that changed since the last call go over the wire
+    pub fn display(&mut self) -> Result<(), DriverError> {
+        self.frame.flush(&mut self.bus)?;
+        Ok(())
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u8) {
+        self.frame.set_pixel(x as u32, y as u32, color);
+    }
+
+    pub fn load_frame(&mut self, frame: &MonoFrame) {
+        self.frame.load_frame(frame);
+    }
+
+    pub fn width(&self) -> usize {
+        self.frame.width() as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.frame.height() as usize
+    }
+
+    pub fn clear(&mut self) {
+        self.frame.clear();
+    }
+}
+
+fn bus_error<E: Debug>(e: E) -> DriverError {
+    DriverError::Bus(format!("{:?}", e))
+}
+
+fn pin_error<E: Debug>(e: E) -> DriverError {
+    DriverError::Pin(format!("{:?}", e))
+}
+
+// Panels on the Pi, over SPI or I2C as set in the settings. Every chip takes the same
+// wiring, the defaults match the Waveshare HATs
+#[cfg(any(feature = "ssd1305", feature = "ssd1306", feature = "sh1106"))]
+pub mod rpi {
+    use super::{Chip, DriverError, OledController, Transport, TransportSettings};
+    use super::{I2cTransport, SpiTransport};
+    use rppal::gpio::{Gpio, OutputPin};
+    use rppal::i2c::I2c;
+    use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+
+    pub type RpiOled<C> = OledController<C, Box<dyn Transport>, OutputPin>;
+
+    fn setup_error(e: &dyn std::error::Error) -> DriverError {
+        DriverError::Setup(e.to_string())
+    }
+
+    fn output_pin(gpio: &Gpio, pin: u8) -> Result<OutputPin, DriverError> {
+        Ok(gpio.get(pin).map_err(|e| setup_error(&e))?.into_output())
+    }
+
+    fn spi_bus(bus: u8) -> Result<Bus, DriverError> {
+        match bus {
+            0 => Ok(Bus::Spi0),
+            1 => Ok(Bus::Spi1),
+            2 => Ok(Bus::Spi2),
+            _ => Err(DriverError::Setup(format!("There's no SPI bus {}", bus))),
+        }
+    }
+
+    fn slave_select(ss: u8) -> Result<SlaveSelect, DriverError> {
+        match ss {
+            0 => Ok(SlaveSelect::Ss0),
+            1 => Ok(SlaveSelect::Ss1),
+            2 => Ok(SlaveSelect::Ss2),
+            _ => Err(DriverError::Setup(format!(
+                "There's no SPI slave select {}",
+                ss
+            ))),
+        }
+    }
+
+    pub fn init<C: Chip>(
+        chip: C,
+        transport: &TransportSettings,
+        width: usize,
+        height: usize,
+    ) -> Result<RpiOled<C>, DriverError> {
+        let gpio = Gpio::new().map_err(|e| setup_error(&e))?;
+
+        let (bus, reset_pin) = match *transport {
+            TransportSettings::Spi {
+                bus,
+                slave_select: ss,
+                speed_hz,
+                dc_pin,
+                reset_pin,
+            } => {
+                let spi = Spi::new(spi_bus(bus)?, slave_select(ss)?, speed_hz, Mode::Mode0)
+                    .map_err(|e| setup_error(&e))?;
+                let dc = output_pin(&gpio, dc_pin)?;
+                let bus: Box<dyn Transport> = Box::new(SpiTransport::new(spi, dc));
+                (bus, reset_pin)
+            }
+            TransportSettings::I2c {
+                bus,
+                address,
+                reset_pin,
+            } => {
+                let i2c = I2c::with_bus(bus).map_err(|e| setup_error(&e))?;
+                let bus: Box<dyn Transport> = Box::new(I2cTransport::new(i2c, address));
+                (bus, reset_pin)
+            }
+        };
+
+        let rst = match reset_pin {
+            Some(pin) => Some(output_pin(&gpio, pin)?),
+            None => None,
+        };
+
+        let mut controller = OledController::new(chip, bus, rst, width, height);
+        controller.init()?;
+        Ok(controller)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_hal::{Event, MockBus, MockPin};
+    use crate::ssd1306::Ssd1306;
+
+    // Stands in for the transport and counts what would go over the wire
+    #[derive(Default)]
+    struct CountingWriter {
+        commands: Vec<u8>,
+        data_bytes: usize,
+    }
+
+    impl Transport for CountingWriter {
+        fn command(&mut self, cmd: u8) -> Result<(), DriverError> {
+            self.commands.push(cmd);
+            Ok(())
+        }
+
+        fn data(&mut self, data: &[u8]) -> Result<(), DriverError> {
+            self.data_bytes += data.len();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_first_flush_sends_everything() {
+        let mut buffer = PageBuffer::new(128, 32, 4);
+        let mut out = CountingWriter::default();
+
+        assert_eq!(buffer.flush(&mut out).unwrap(), 512);
+        assert_eq!(out.data_bytes, 512);
+        assert_eq!(&out.commands[0..6], &[0xB0, 0x04, 0x10, 0xB1, 0x04, 0x10]);
+    }
+
+    #[test]
+    fn test_unchanged_frame_sends_nothing() {
+        let mut buffer = PageBuffer::new(128, 32, 4);
+        buffer.flush(&mut CountingWriter::default()).unwrap();
+
+        let mut out = CountingWriter::default();
+        assert_eq!(buffer.flush(&mut out).unwrap(), 0);
+        assert!(out.commands.is_empty());
+    }
+
+    #[test]
+    fn test_only_changed_columns_are_sent() {
+        let mut buffer = PageBuffer::new(128, 32, 4);
+        buffer.flush(&mut CountingWriter::default()).unwrap();
+
+        //Blinking colon of the clock, column 7 rows 1 and 3
+        buffer.set_pixel(7, 1, 1);
+        buffer.set_pixel(7, 3, 1);
+        let mut out = CountingWriter::default();
+
+        assert_eq!(buffer.flush(&mut out).unwrap(), 1);
+        //Column 7 + 4 offset = 0x0B
+        assert_eq!(out.commands, vec![0xB0, 0x0B, 0x10]);
+
+        //Changes spread over a page are sent as one span
+        buffer.set_pixel(20, 9, 1);
+        buffer.set_pixel(100, 12, 1);
+        let mut out = CountingWriter::default();
+
+        assert_eq!(buffer.flush(&mut out).unwrap(), 81);
+        assert_eq!(
+            buffer.dirty_spans(),
+            vec![],
+            "Flushing should mark everything as sent"
+        );
+        //Column 20 + 4 offset = 0x18
+        assert_eq!(out.commands, vec![0xB1, 0x08, 0x11]);
+    }
+
+    #[test]
+    fn test_invalidate_resends_everything() {
+        let mut buffer = PageBuffer::new(128, 32, 4);
+        buffer.flush(&mut CountingWriter::default()).unwrap();
+
+        buffer.invalidate();
+
+        assert_eq!(buffer.flush(&mut CountingWriter::default()).unwrap(), 512);
+    }
+
+    #[test]
+    fn test_load_frame() {
+        let mut frame = MonoFrame::new(128, 32);
+        frame.set_pixel(5, 9, true);
+        let mut buffer = PageBuffer::new(128, 32, 4);
+        buffer.flush(&mut CountingWriter::default()).unwrap();
+
+        buffer.load_frame(&frame);
+
+        assert_eq!(
+            buffer.dirty_spans(),
+            vec![DirtySpan {
+                page: 1,
+                start: 5,
+                end: 6
+            }]
+        );
+    }
+
+    #[test]
+    fn test_oversized_frame_is_clipped() {
+        let mut frame = MonoFrame::new(200, 40);
+        frame.set_pixel(3, 3, true);
+        frame.set_pixel(150, 35, true);
+        let mut buffer = PageBuffer::new(128, 32, 4);
+        buffer.flush(&mut CountingWriter::default()).unwrap();
+
+        buffer.load_frame(&frame);
+        buffer.load_frame(&frame);
+
+        assert_eq!(buffer.mismatched, Some((200, 40)));
+        assert_eq!(
+            buffer.dirty_spans(),
+            vec![DirtySpan {
+                page: 0,
+                start: 3,
+                end: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn test_spi_framing() {
+        let bus = MockBus::default();
+        let mut transport = SpiTransport::new(bus.spi(), bus.dc());
+
+        transport.command(0xAF).unwrap();
+        transport.data(&[1, 2, 3]).unwrap();
+
+        //The DC pin tells them apart, the bytes go over as they are
+        assert_eq!(
+            bus.events(),
+            vec![Event::Command(0xAF), Event::Data(vec![1, 2, 3])]
+        );
+    }
+
+    #[test]
+    fn test_i2c_framing() {
+        let bus = MockBus::default();
+        let mut transport = I2cTransport::new(bus.i2c(), 0x3C);
+
+        transport.command(0xAF).unwrap();
+        transport.data(&[1, 2, 3]).unwrap();
+
+        assert_eq!(
+            bus.events(),
+            vec![
+                Event::I2c(0x3C, vec![0x00, 0xAF]),
+                Event::I2c(0x3C, vec![0x40, 1, 2, 3])
+            ]
+        );
+
+        bus.fail_writes(true);
+        assert!(matches!(transport.command(0xAE), Err(DriverError::Bus(_))));
+    }
+
+    #[test]
+    fn test_controller_over_i2c_without_reset() {
+        let bus = MockBus::default();
+        let transport = I2cTransport::new(bus.i2c(), 0x3D);
+        let mut controller = OledController::new(Ssd1306, transport, None::<MockPin>, 128, 32);
+
+        controller.init().unwrap();
+
+        //No reset pin to toggle, every write is framed with its control byte
+        let events = bus.events();
+        assert_eq!(events[0], Event::I2c(0x3D, vec![0x00, 0xAE]));
+        assert!(events.iter().all(|e| match e {
+            Event::I2c(0x3D, bytes) => bytes[0] == 0x00 || bytes[0] == 0x40,
+            _ => false,
+        }));
+
+        let data: usize = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::I2c(_, bytes) if bytes[0] == 0x40 => Some(bytes.len() - 1),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(data, 512);
+    }
+
+    #[test]
+    fn test_transport_settings() {
+        let spi: TransportSettings = toml::from_str("type = \"spi\"\ndc_pin = 22").unwrap();
+        assert_eq!(
+            spi,
+            TransportSettings::Spi {
+                bus: 0,
+                slave_select: 0,
+                speed_hz: 8_000_000,
+                dc_pin: 22,
+                reset_pin: Some(25),
+            }
+        );
+
+        let i2c: TransportSettings = toml::from_str("type = \"i2c\"\naddress = 0x3D").unwrap();
+        assert_eq!(
+            i2c,
+            TransportSettings::I2c {
+                bus: 1,
+                address: 0x3D,
+                reset_pin: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+//Running from a checkout keeps everything in resources/ like it always has
+const CHECKOUT_CONFIG: &str = "resources/Settings.toml";
+const FONT: &str = "Font-5px.png";
+
+// Where the clock finds its configuration, keeps the tide data and loads the font from.
+// See Paths::locate for how they're picked
+#[derive(Debug, PartialEq, Clone)]
+pub struct Paths {
+    //Settings.toml, with Layout.toml and Secrets.toml next to it
+    pub config: PathBuf,
+    //tides.json, the last response from the API, and the image writer's display.bmp
+    pub data_dir: PathBuf,
+    //Font-5px.png, if the built in font is to be replaced
+    pub assets_dir: PathBuf,
+}
+
+// Everything in resources/, as in a checkout
+impl Default for Paths {
+    fn default() -> Paths {
+        Paths {
+            config: PathBuf::from(CHECKOUT_CONFIG),
+            data_dir: PathBuf::from("resources"),
+            assets_dir: PathBuf::from("resources"),
+        }
+    }
+}
+
+impl Paths {
+    // Each of TIDE_CLOCK_CONFIG, TIDE_CLOCK_DATA_DIR and TIDE_CLOCK_ASSETS_DIR wins when set.
+    // Otherwise resources/ when run from a checkout, and the XDG base directories when
+    // installed: ~/.config/tide-clock, ~/.cache/tide-clock and ~/.local/share/tide-clock or
+    // /usr/share/tide-clock for the assets
+    pub fn locate() -> Paths {
+        Paths::resolve(|name| env::var_os(name), |path| path.exists())
+    }
+
+    fn resolve<V, E>(var: V, exists: E) -> Paths
+    where
+        V: Fn(&str) -> Option<OsString>,
+        E: Fn(&Path) -> bool,
+    {
+        let set = |name: &str| var(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+
+        if exists(Path::new(CHECKOUT_CONFIG)) {
+            let checkout = Paths::default();
+            return Paths {
+                config: set("TIDE_CLOCK_CONFIG").unwrap_or(checkout.config),
+                data_dir: set("TIDE_CLOCK_DATA_DIR").unwrap_or(checkout.data_dir),
+                assets_dir: set("TIDE_CLOCK_ASSETS_DIR").unwrap_or(checkout.assets_dir),
+            };
+        }
+
+        //Relative XDG variables are meant to be ignored, as is a missing HOME
+        let home = set("HOME").filter(|h| h.is_absolute());
+        let base = |name: &str, fallback: &str| {
+            set(name)
+                .filter(|p| p.is_absolute())
+                .or_else(|| home.as_ref().map(|h| h.join(fallback)))
+                .map(|dir| dir.join("tide-clock"))
+        };
+
+        let config_dir = base("XDG_CONFIG_HOME", ".config");
+        let data_home = base("XDG_DATA_HOME", ".local/share");
+
+        //The assets can be installed system wide, the first directory that has them is used
+        let data_dirs = var("XDG_DATA_DIRS")
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| OsString::from("/usr/local/share:/usr/share"));
+        let installed = data_home
+            .clone()
+            .into_iter()
+            .chain(
+                env::split_paths(&data_dirs)
+                    .filter(|p| p.is_absolute())
+                    .map(|dir| dir.join("tide-clock")),
+            )
+            .find(|dir| exists(&dir.join(FONT)));
+
+        Paths {
+            config: set("TIDE_CLOCK_CONFIG").unwrap_or_else(|| match config_dir {
+                Some(dir) => dir.join("Settings.toml"),
+                None => PathBuf::from(CHECKOUT_CONFIG),
+            }),
+            data_dir: set("TIDE_CLOCK_DATA_DIR")
+                .or_else(|| base("XDG_CACHE_HOME", ".cache"))
+                .unwrap_or_else(|| PathBuf::from("resources")),
+            assets_dir: set("TIDE_CLOCK_ASSETS_DIR")
+                .or(installed)
+                .or(data_home)
+                .unwrap_or_else(|| PathBuf::from("resources")),
+        }
+    }
+
+    pub fn settings(&self) -> &Path {
+        &self.config
+    }
+
+    pub fn layout(&self) -> PathBuf {
+        self.beside_config("Layout.toml")
+    }
+
+    pub fn secrets(&self) -> PathBuf {
+        self.beside_config("Secrets.toml")
+    }
+
+    pub fn tides(&self) -> PathBuf {
+        self.data_dir.join("tides.json")
+    }
+
+    //Where the image writer saves its frames
+    pub fn display_image(&self) -> PathBuf {
+        self.data_dir.join("display.bmp")
+    }
+
+    pub fn font(&self) -> PathBuf {
+        self.assets_dir.join(FONT)
+    }
+
+    fn beside_config(&self, name: &str) -> PathBuf {
+        match self.config.parent() {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn resolve(vars: &[(&str, &str)], existing: &[&str]) -> Paths {
+        let vars: HashMap<_, _> = vars.iter().cloned().collect();
+        Paths::resolve(
+            |name| vars.get(name).map(OsString::from),
+            |path| existing.iter().any(|e| Path::new(e) == path),
+        )
+    }
+
+    #[test]
+    fn test_files_sit_beside_the_config() {
+        let paths = Paths {
+            config: PathBuf::from("/etc/tide-clock/Settings.toml"),
+            data_dir: PathBuf::from("/var/cache/tide-clock"),
+            assets_dir: PathBuf::from("/usr/share/tide-clock"),
+        };
+
+        assert_eq!(paths.layout(), Path::new("/etc/tide-clock/Layout.toml"));
+        assert_eq!(paths.secrets(), Path::new("/etc/tide-clock/Secrets.toml"));
+        assert_eq!(paths.tides(), Path::new("/var/cache/tide-clock/tides.json"));
+        assert_eq!(
+            paths.font(),
+            Path::new("/usr/share/tide-clock/Font-5px.png")
+        );
+
+        let bare = Paths {
+            config: PathBuf::from("Settings.toml"),
+            ..Paths::default()
+        };
+        assert_eq!(bare.layout(), Path::new("Layout.toml"));
+        assert_eq!(Paths::default().tides(), Path::new("resources/tides.json"));
+    }
+
+    #[test]
+    fn test_checkout_uses_resources() {
+        let home = [("HOME", "/home/pi")];
+        assert_eq!(resolve(&home, &[CHECKOUT_CONFIG]), Paths::default());
+
+        let paths = resolve(
+            &[("HOME", "/home/pi"), ("TIDE_CLOCK_DATA_DIR", "/tmp/tides")],
+            &[CHECKOUT_CONFIG],
+        );
+        assert_eq!(paths.data_dir, Path::new("/tmp/tides"));
+        assert_eq!(paths.config, Path::new(CHECKOUT_CONFIG));
+    }
+
+    #[test]
+    fn test_installed_uses_xdg() {
+        let paths = resolve(&[("HOME", "/home/pi")], &[]);
+        assert_eq!(
+            paths.config,
+            Path::new("/home/pi/.config/tide-clock/Settings.toml")
+        );
+        assert_eq!(paths.data_dir, Path::new("/home/pi/.cache/tide-clock"));
+        assert_eq!(
+            paths.assets_dir,
+            Path::new("/home/pi/.local/share/tide-clock")
+        );
+
+        let paths = resolve(
+            &[
+                ("HOME", "/home/pi"),
+                ("XDG_CONFIG_HOME", "/srv/config"),
+                ("XDG_CACHE_HOME", "relative/is/ignored"),
+                ("TIDE_CLOCK_ASSETS_DIR", "/opt/tide-clock"),
+            ],
+            &[],
+        );
+        assert_eq!(
+            paths.config,
+            Path::new("/srv/config/tide-clock/Settings.toml")
+        );
+        assert_eq!(paths.data_dir, Path::new("/home/pi/.cache/tide-clock"));
+        assert_eq!(paths.assets_dir, Path::new("/opt/tide-clock"));
+    }
+
+    #[test]
+    fn test_assets_installed_system_wide() {
+        let paths = resolve(
+            &[("HOME", "/home/pi")],
+            &["/usr/share/tide-clock/Font-5px.png"],
+        );
+        assert_eq!(paths.assets_dir, Path::new("/usr/share/tide-clock"));
+
+        let paths = resolve(
+            &[("HOME", "/home/pi"), ("XDG_DATA_DIRS", "/opt/share")],
+            &["/opt/share/tide-clock/Font-5px.png"],
+        );
+        assert_eq!(paths.assets_dir, Path::new("/opt/share/tide-clock"));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::display::{DisplayControl, EmulatedControls, RenderDevice};
+use crate::mono_frame::MonoFrame;
+use image::{imageops, DynamicImage, ImageOutputFormat};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// The [display.preview] section of Settings.toml
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct PreviewSettings {
+    //Only reachable from this machine by default, "0.0.0.0" to watch from elsewhere
+    #[serde(default = "default_address")]
+    pub address: String,
+    //0 picks a free port
+    #[serde(default = "default_port")]
+    pub port: u16,
+    //Each pixel becomes a scale x scale square, 128x32 is tiny in a browser
+    #[serde(default = "default_scale")]
+    pub scale: u32,
+}
+
+fn default_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_port() -> u16 {
+    8128
+}
+
+fn default_scale() -> u32 {
+    4
+}
+
+impl Default for PreviewSettings {
+    fn default() -> PreviewSettings {
+        PreviewSettings {
+            address: default_address(),
+            port: default_port(),
+            scale: default_scale(),
+        }
+    }
+}
+
+//Seconds between reloads of the page
+const REFRESH: u32 = 1;
+
+// Serves the last frame over HTTP: a page at / that reloads itself, and the frame as an
+// upscaled PNG at /frame.png. Connections are answered one at a time on a background thread,
+// which is plenty for a few people watching
+pub struct PreviewServer {
+    width: u32,
+    height: u32,
+    scale: u32,
+    address: SocketAddr,
+    //PNG of the last frame, shared with the server thread
+    png: Arc<Mutex<Vec<u8>>>,
+    controls: EmulatedControls,
+}
+
+impl PreviewServer {
+    pub fn bind(
+        width: u32,
+        height: u32,
+        settings: &PreviewSettings,
+    ) -> Result<PreviewServer, Box<dyn Error>> {
+        let listener = TcpListener::bind((settings.address.as_str(), settings.port))?;
+
+        let mut server = PreviewServer {
+            width,
+            height,
+            scale: settings.scale.max(1),
+            address: listener.local_addr()?,
+            png: Arc::new(Mutex::new(Vec::new())),
+            controls: EmulatedControls::default(),
+        };
+        //Something to show before the first frame
+        server.render(&MonoFrame::new(width, height))?;
+
+        let png = server.png.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|s| respond(s, &png));
+                if let Err(e) = result {
+                    println!("Preview request failed. Err {}", e);
+                }
+            }
+        });
+
+        Ok(server)
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+fn respond(stream: TcpStream, png: &Mutex<Vec<u8>>) -> io::Result<()> {
+    //Don't let a client that never finishes its request hold up everyone else
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut reader = BufReader::new(stream);
+
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    //The headers don't matter, but closing with them unread can reset the connection
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let (status, content_type, body) = match path {
+        "/" | "/index.html" => ("200 OK", "text/html; charset=utf-8", page().into_bytes()),
+        "/frame.png" => ("200 OK", "image/png", png.lock().unwrap().clone()),
+        _ => ("404 Not Found", "text/plain", b"Not found".to_vec()),
+    };
+
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
+
+fn page() -> String {
+    format!(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head><title>Tide clock</title><meta http-equiv=\"refresh\" content=\"{}\"></head>\n\
+         <body style=\"background: #222\">\n\
+         <img src=\"/frame.png\" alt=\"Tide clock\" style=\"image-rendering: pixelated\">\n\
+         </body>\n\
+         </html>\n",
+        REFRESH
+    )
+}
+
+impl RenderDevice for PreviewServer {
+    fn render(&mut self, buffer: &MonoFrame) -> Result<(), Box<dyn Error>> {
+        let img = self.controls.to_rgb(buffer);
+        let img = imageops::resize(
+            &img,
+            img.width() * self.scale,
+            img.height() * self.scale,
+            imageops::FilterType::Nearest,
+        );
+
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(img).write_to(&mut png, ImageOutputFormat::Png)?;
+        *self.png.lock().unwrap() = png;
+
+        Ok(())
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn controls(&mut self) -> Option<&mut dyn DisplayControl> {
+        Some(&mut self.controls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn server() -> PreviewServer {
+        let settings = PreviewSettings {
+            port: 0,
+            ..PreviewSettings::default()
+        };
+        PreviewServer::bind(128, 32, &settings).unwrap()
+    }
+
+    // Status line, headers and body of a GET
+    fn get(server: &PreviewServer, path: &str) -> (String, Vec<u8>) {
+        let mut stream = TcpStream::connect(server.address()).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+
+        let head = String::from_utf8(response[..split].to_vec()).unwrap();
+        (head, response[split + 4..].to_vec())
+    }
+
+    #[test]
+    fn test_serves_upscaled_frame() {
+        let mut server = server();
+        let mut frame = MonoFrame::new(128, 32);
+        frame.set_pixel(1, 2, true);
+        server.render(&frame).unwrap();
+
+        let (head, body) = get(&server, "/frame.png");
+
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        assert!(head.contains("Content-Type: image/png"));
+        let img = image::load_from_memory(&body).unwrap();
+        let img = img.as_rgb8().unwrap();
+        assert_eq!(img.dimensions(), (512, 128));
+        //Pixel 1, 2 covers 4..8, 8..12
+        assert_eq!(img.get_pixel(4, 8)[0], 255);
+        assert_eq!(img.get_pixel(7, 11)[0], 255);
+        assert_eq!(img.get_pixel(8, 8)[0], 0);
+    }
+
+    #[test]
+    fn test_serves_page() {
+        let server = server();
+
+        let (head, body) = get(&server, "/");
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.contains("<img src=\"/frame.png\""));
+        assert!(body.contains("http-equiv=\"refresh\""));
+
+        let (head, _) = get(&server, "/favicon.ico");
+        assert!(head.starts_with("HTTP/1.1 404"));
+    }
+}
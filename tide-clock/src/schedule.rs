@@ -0,0 +1,77 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::time;
+
+//Longest the main loop sleeps, even when nothing on screen is due to change. Keeps the tide
+//data and night mode checked on
+pub const MAX_SLEEP: time::Duration = time::Duration::from_secs(60);
+
+//Woken a little after a change is due, so the frame is composed on the right side of it
+const MARGIN: time::Duration = time::Duration::from_millis(5);
+
+// The next time after `now` that's a whole number of `period` seconds since the epoch, e.g.
+// the start of the next minute for 60
+pub fn next_boundary<Tz: TimeZone>(now: &DateTime<Tz>, period: i64) -> DateTime<Utc> {
+    let now = now.with_timezone(&Utc);
+    let seconds = now.timestamp();
+    let next = (seconds.div_euclid(period.max(1)) + 1) * period.max(1);
+
+    now + Duration::seconds(next - seconds)
+        - Duration::nanoseconds(now.timestamp_subsec_nanos() as i64)
+}
+
+// How long to sleep to wake just after the earliest of `changes`
+pub fn sleep_until<I>(now: DateTime<Utc>, changes: I) -> time::Duration
+where
+    I: IntoIterator<Item = Option<DateTime<Utc>>>,
+{
+    let wake = match changes.into_iter().flatten().min() {
+        Some(wake) => wake,
+        None => return MAX_SLEEP,
+    };
+
+    match wake.signed_duration_since(now).to_std() {
+        Ok(duration) => (duration + MARGIN).min(MAX_SLEEP),
+        //Already due
+        Err(_) => time::Duration::from_millis(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::fixture_time;
+
+    #[test]
+    fn test_next_boundary() {
+        let now = fixture_time("2020-10-02T19:00:01Z") + Duration::milliseconds(250);
+
+        assert_eq!(next_boundary(&now, 1), fixture_time("2020-10-02T19:00:02Z"));
+        assert_eq!(
+            next_boundary(&now, 60),
+            fixture_time("2020-10-02T19:01:00Z")
+        );
+        //Exactly on a boundary it's the next one
+        let now = fixture_time("2020-10-02T19:01:00Z");
+        assert_eq!(
+            next_boundary(&now, 60),
+            fixture_time("2020-10-02T19:02:00Z")
+        );
+    }
+
+    #[test]
+    fn test_sleep_until() {
+        let now = fixture_time("2020-10-02T19:00:00Z");
+        let at = |seconds| Some(now + Duration::seconds(seconds));
+
+        assert_eq!(
+            sleep_until(now, vec![at(30), None, at(2)]),
+            time::Duration::from_secs(2) + MARGIN
+        );
+        assert_eq!(sleep_until(now, vec![at(600)]), MAX_SLEEP);
+        assert_eq!(sleep_until(now, vec![None]), MAX_SLEEP);
+        assert_eq!(
+            sleep_until(now, vec![at(-1)]),
+            time::Duration::from_millis(0)
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use crate::burn_in::BurnInSettings;
+use crate::display::Backend;
+use crate::framebuffer::FramebufferSettings;
+use crate::mirror::MirrorSettings;
+use crate::night::NightSettings;
+use crate::oled::TransportSettings;
+use crate::preview::PreviewSettings;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{error::Error, fs, path::Path};
+
+pub fn load_config<T, P>(path: P) -> Result<T, Box<dyn Error>>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let rawdata = fs::read_to_string(path).map_err(|err| {
+        simple_error::SimpleError::new(format!("{1} Filename {0}:", path.display(), err))
+    })?;
+
+    let result = toml::from_str(&rawdata)
+        .map_err(|err| simple_error::SimpleError::new(format!("{}: {}", path.display(), err)))?;
+
+    Ok(result)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Secrets {
+    pub key: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Settings {
+    pub lon: String,
+    pub lat: String,
+    pub step: String,
+    pub datum: String,
+
+    #[serde(default)]
+    pub display: DisplaySettings,
+
+    //No night mode when left out
+    #[serde(default)]
+    pub night: Option<NightSettings>,
+
+    #[serde(default)]
+    pub burn_in: BurnInSettings,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct DisplaySettings {
+    pub width: u32,
+    pub height: u32,
+
+    //Falls back to the panel if it's built in, see Backend::default
+    #[serde(default)]
+    pub backend: Option<Backend>,
+
+    //0 to 255, the panel's own default when not set
+    #[serde(default)]
+    pub contrast: Option<u8>,
+
+    //For a panel mounted upside down
+    #[serde(default)]
+    pub rotate_180: bool,
+
+    //Dark face on a lit background
+    #[serde(default)]
+    pub invert: bool,
+
+    //How a panel is wired, SPI on the Waveshare pins when not set
+    #[serde(default)]
+    pub transport: TransportSettings,
+
+    //Where the preview-server backend listens
+    #[serde(default)]
+    pub preview: PreviewSettings,
+
+    //Device and colours for the framebuffer backend
+    #[serde(default)]
+    pub framebuffer: FramebufferSettings,
+
+    //More devices that get the same frames as the backend
+    #[serde(default)]
+    pub mirror: Vec<MirrorSettings>,
+}
+
+impl Default for DisplaySettings {
+    // The Waveshare 2.23inch OLED HAT
+    fn default() -> DisplaySettings {
+        DisplaySettings {
+            width: 128,
+            height: 32,
+            backend: None,
+            contrast: None,
+            rotate_180: false,
+            invert: false,
+            transport: TransportSettings::default(),
+            preview: PreviewSettings::default(),
+            framebuffer: FramebufferSettings::default(),
+            mirror: vec![],
+        }
+    }
+}
+
+impl Settings {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Settings, Box<dyn Error>> {
+        load_config(path)
+    }
+}
@@ -0,0 +1,107 @@
+use crate::oled::{Chip, PanelState};
+
+// The 1.3" breakout panels, 128x64. Close to the SSD1306, but with 132 columns of display RAM,
+// only page addressing and no hardware scroll
+pub struct Sh1106;
+
+impl Chip for Sh1106 {
+    fn init_sequence(&self, height: u32, state: &PanelState) -> Vec<u8> {
+        vec![
+            0xAE, //Display off
+            0xD5, //Set display clock divide ratio/oscillator frequency
+            0x80,
+            0xA8, //Set multiplex ratio
+            (height - 1) as u8,
+            0xD3, //Set display offset
+            0x00,
+            0x40, //Set display start line to 0
+            0xAD, //DC-DC control
+            0x8B, //Built in DC-DC on
+            state.segment_remap(),
+            state.com_scan_direction(),
+            0xDA, //Set COM pins hardware configuration
+            0x12,
+            0x81, //Set contrast control
+            state.contrast,
+            0xD9, //Set pre-charge period
+            0x22,
+            0xDB, //Set VCOM deselect level
+            0x35,
+            0xA4, //Show the RAM contents
+            state.display_mode(),
+            state.display_power(),
+        ]
+    }
+
+    //The 128 visible columns sit in the middle of the 132 in display RAM, whichever way
+    //the segments are mapped
+    fn column_offset(&self, _rotated: bool) -> u8 {
+        2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_hal::{MockBus, MockPin, MockSpi};
+    use crate::mono_frame::MonoFrame;
+    use crate::oled::{DriverError, OledController, SpiTransport};
+
+    fn controller(
+        bus: &MockBus,
+    ) -> OledController<Sh1106, SpiTransport<MockSpi, MockPin>, MockPin> {
+        OledController::new(
+            Sh1106,
+            SpiTransport::new(bus.spi(), bus.dc()),
+            Some(bus.rst()),
+            128,
+            64,
+        )
+    }
+
+    #[test]
+    fn test_init_sequence() {
+        let bus = MockBus::default();
+        controller(&bus).init().unwrap();
+
+        let commands = bus.commands();
+        assert_eq!(commands[0], 0xAE);
+        assert!(commands.windows(2).any(|w| w == [0xAD, 0x8B]));
+        assert_eq!(commands[22], 0xAF, "Panel on last");
+        assert_eq!(bus.data().len(), 1024);
+    }
+
+    #[test]
+    fn test_page_writes_are_offset_by_two_columns() {
+        let bus = MockBus::default();
+        let mut controller = controller(&bus);
+        controller.init().unwrap();
+        bus.clear();
+
+        let mut frame = MonoFrame::new(128, 64);
+        frame.set_pixel(0, 0, true);
+        frame.set_pixel(0, 20, true);
+        controller.load_frame(&frame);
+        controller.display().unwrap();
+
+        //Column 0 + 2, the first page only lost its top right corner
+        let commands = bus.commands();
+        assert_eq!(&commands[0..3], &[0xB0, 0x01, 0x18]);
+        assert_eq!(&commands[3..6], &[0xB2, 0x02, 0x10]);
+    }
+
+    #[test]
+    fn test_no_hardware_scroll() {
+        let bus = MockBus::default();
+        let mut controller = controller(&bus);
+        controller.init().unwrap();
+        bus.clear();
+
+        assert!(!controller.can_scroll());
+        assert!(matches!(
+            controller.start_scroll(),
+            Err(DriverError::Unsupported(_))
+        ));
+        assert!(bus.events().is_empty());
+    }
+}
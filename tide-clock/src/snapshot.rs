@@ -0,0 +1,134 @@
+// Golden image harness for the painters. Frames are compared against PNGs committed in
+// tests/snapshots. Run with UPDATE_SNAPSHOTS=1 to (re)write the golden images after an
+// intentional change to the renderer.
+use crate::font;
+use crate::layout::{FrameContext, Layout};
+use crate::mono_frame::MonoFrame;
+use crate::tides::{TideModel, TideResponse};
+use chrono::{DateTime, Local, Utc};
+use image::{Rgb, RgbImage};
+use std::{env, fs, path::PathBuf, sync::Once};
+
+const SNAPSHOT_DIR: &str = "tests/snapshots";
+const OUTPUT_DIR: &str = "target/snapshots";
+const FIXTURE_TIDES: &str = include_str!("../tests/fixtures/tides.json");
+
+static INIT_TZ: Once = Once::new();
+
+// Labels and the clock are formatted in local time, so pin the time zone before anything
+// touches chrono::Local. Otherwise the golden images would depend on the machine running the tests
+pub fn init_tz() {
+    INIT_TZ.call_once(|| env::set_var("TZ", "UTC"));
+}
+
+pub fn fixture_model() -> TideModel {
+    let response: TideResponse = serde_json::from_str(FIXTURE_TIDES).unwrap();
+    TideModel::new(response)
+}
+
+pub fn fixture_time(rfc3339: &str) -> DateTime<Utc> {
+    rfc3339.parse::<DateTime<Utc>>().unwrap()
+}
+
+// The fixture tides at `at` on the clock's 128x32 screen, the way the loop sets up a frame
+pub fn with_fixture_ctx<R>(at: &str, f: impl FnOnce(&FrameContext) -> R) -> R {
+    with_fixture_screen((128, 32), at, f)
+}
+
+// The same on another screen, with the window as wide as the default face's graph
+pub fn with_fixture_screen<R>(
+    screen: (u32, u32),
+    at: &str,
+    f: impl FnOnce(&FrameContext) -> R,
+) -> R {
+    init_tz();
+    let font = font::init();
+    let tide_model = fixture_model();
+    let local_time = fixture_time(at).with_timezone(&Local);
+    let (tide_window, _) = tide_model.get_window(local_time, Layout::default().graph_width(screen));
+
+    f(&FrameContext {
+        screen,
+        font: &font,
+        tide_model: &tide_model,
+        tide_window: &tide_window,
+        local_time,
+    })
+}
+
+pub fn assert_snapshot(name: &str, frame: &MonoFrame) {
+    let actual = &frame.to_rgb();
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let golden_path = root.join(SNAPSHOT_DIR).join(format!("{}.png", name));
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(root.join(SNAPSHOT_DIR)).unwrap();
+        actual.save(&golden_path).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&golden_path) {
+        Ok(img) => img
+            .as_rgb8()
+            .cloned()
+            .expect("Golden image is not 8 bit RGB"),
+        Err(e) => panic!(
+            "Missing golden image {:?} ({}). Run with UPDATE_SNAPSHOTS=1 to create it",
+            golden_path, e
+        ),
+    };
+
+    let mismatches = count_mismatches(&expected, actual);
+    if mismatches == 0 {
+        return;
+    }
+
+    let out_dir = root.join(OUTPUT_DIR);
+    fs::create_dir_all(&out_dir).unwrap();
+    let actual_path = out_dir.join(format!("{}.actual.png", name));
+    let diff_path = out_dir.join(format!("{}.diff.png", name));
+    actual.save(&actual_path).unwrap();
+    diff_image(&expected, actual).save(&diff_path).unwrap();
+
+    panic!(
+        "Snapshot '{}' differs by {} pixels. Actual: {:?} Diff: {:?}",
+        name, mismatches, actual_path, diff_path
+    );
+}
+
+fn count_mismatches(expected: &RgbImage, actual: &RgbImage) -> usize {
+    if expected.dimensions() != actual.dimensions() {
+        return (expected.width() * expected.height()).max(actual.width() * actual.height())
+            as usize;
+    }
+
+    expected
+        .pixels()
+        .zip(actual.pixels())
+        .filter(|(a, b)| a != b)
+        .count()
+}
+
+// Matching pixels are dimmed so the shape stays recognisable, differences are drawn in red
+fn diff_image(expected: &RgbImage, actual: &RgbImage) -> RgbImage {
+    let width = expected.width().max(actual.width());
+    let height = expected.height().max(actual.height());
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let a = in_bounds(expected, x, y);
+        let b = in_bounds(actual, x, y);
+
+        match (a, b) {
+            (Some(a), Some(b)) if a == b => Rgb([a[0] / 4, a[1] / 4, a[2] / 4]),
+            _ => Rgb([255, 0, 0]),
+        }
+    })
+}
+
+fn in_bounds(img: &RgbImage, x: u32, y: u32) -> Option<Rgb<u8>> {
+    if x < img.width() && y < img.height() {
+        Some(*img.get_pixel(x, y))
+    } else {
+        None
+    }
+}
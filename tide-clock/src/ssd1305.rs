@@ -1,154 +1,212 @@
-use rppal::gpio;
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
-use std::thread;
-use std::time::Duration;
-
-//BCM pin numbers
-const GPIO_DC: u8 = 24;
-const GPIO_RST: u8 = 25;
-const WIDTH: usize = 128;
-const HEIGHT: usize = 32;
-
-pub struct Ssd1305Controller {
-    gpio_dc: gpio::OutputPin,
-    gpio_rst: gpio::OutputPin,
-    spi: Spi,
-
-    buffer: [u8; 512],
-}
-
-impl Ssd1305Controller {
-    fn command(&mut self, cmd: u8) {
-        //println!("{}", cmd);
-
-        &self.gpio_dc.write(gpio::Level::Low);
-
-        let write_buffer = vec![cmd]; //Could make this a look up for less memory pressure
-
-        &self.spi.write(&write_buffer).unwrap();
+use crate::oled::{Chip, PanelState, SCROLL_INTERVAL};
+
+// The Waveshare 2.23inch OLED HAT, 128x32
+pub struct Ssd1305;
+
+impl Chip for Ssd1305 {
+    fn init_sequence(&self, height: u32, state: &PanelState) -> Vec<u8> {
+        vec![
+            0xAE, //Display off
+            0x04, //Set lower column start address
+            0x10, //Set higher column start address
+            0x40, //Set display start line to 0
+            0x81, //Set contrast control
+            state.contrast,
+            state.segment_remap(), //Column mapping, flipped when rotated
+            state.display_mode(),  //Normal or inverted
+            0xA8,                  //Set multiplex ratio
+            (height - 1) as u8,
+            state.com_scan_direction(), //Row scan direction, flipped when rotated
+            0xD3,                       //Set display offset
+            0x00,
+            0xD5, //Set display clock divide ratio/oscillator frequency
+            0xF0,
+            0xD8, //Set area colour mode and low power display mode
+            0x05, //Monochrome, low power
+            0xD9, //Set pre-charge period
+            0xC2,
+            0xDA, //Set COM pins hardware configuration
+            0x12,
+            0xDB, //Set VCOMH deselect level
+            0x08,
+            state.display_power(), //Display on, or kept off if it was switched off
+        ]
     }
 
-    pub fn display(&mut self) {
-        //write the buffer
-
-        for page in 0..4 {
-            self.command(0xB0 + page); //Set page address
-            self.command(0x04); //Set low column address
-            self.command(0x10); //Set high column address
-            self.gpio_dc.write(gpio::Level::High);
-
-            //Typing shennigans
-            let page_usize = page as usize;
-            let start_index: usize = page_usize * WIDTH;
-            let end_index: usize = start_index + WIDTH;
-
-            //Every 8 rows is a represented in a 128 bit buffer
-            let page_slice = &self.buffer[start_index..end_index];
-
-            self.spi.write(page_slice).unwrap();
-        }
-    }
-
-    pub fn set_pixel(&mut self, x: usize, y: usize, color: u8) {
-        if x >= WIDTH || y >= HEIGHT {
-            println!("SetColor: Pixel out of bounds x:{} y:{}", x, y);
-            return;
-        }
-
-        //The 128x32 display is split into 4 strips of 128x8,
-        //where each column of 8 is encoded into a single value
-
-        if color > 0 {
-            self.buffer[x + (y / 8) * WIDTH] |= 1 << (y % 8);
-        } else {
-            self.buffer[x + (y / 8) * WIDTH] &= !(1 << (y % 8));
+    //The SSD1305 has 132 columns of display RAM, the panel shows columns 4 to 131. With the
+    //segment remap flipped for a rotated panel the visible columns are 0 to 127 instead
+    fn column_offset(&self, rotated: bool) -> u8 {
+        match rotated {
+            true => 0,
+            false => 4,
         }
     }
 
-    pub fn clear(&mut self) {
-        for i in 0..self.buffer.len() {
-            self.buffer[i] = 0;
-        }
+    fn scroll_setup(&self, direction: u8, end_page: u8) -> Option<Vec<u8>> {
+        Some(vec![
+            direction,
+            0x01, //One column per step
+            0x00, //Start page
+            SCROLL_INTERVAL,
+            end_page,
+        ])
     }
 }
 
-fn setup() -> Ssd1305Controller {
-    let gpio = gpio::Gpio::new().unwrap();
-    let spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 8_000_000, Mode::Mode0).unwrap();
-
-    let gpio_dc = gpio.get(GPIO_DC).unwrap().into_output();
-    let gpio_rst = gpio.get(GPIO_RST).unwrap().into_output();
-
-    let buffer = [0x00; 512];
-
-    Ssd1305Controller {
-        gpio_dc,
-        gpio_rst,
-        spi: spi,
-        buffer: buffer,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_hal::{Event, MockBus, MockPin, MockSpi};
+    use crate::mono_frame::MonoFrame;
+    use crate::oled::{DriverError, OledController, SpiTransport};
+
+    fn controller(
+        bus: &MockBus,
+    ) -> OledController<Ssd1305, SpiTransport<MockSpi, MockPin>, MockPin> {
+        OledController::new(
+            Ssd1305,
+            SpiTransport::new(bus.spi(), bus.dc()),
+            Some(bus.rst()),
+            128,
+            32,
+        )
     }
-}
 
-pub fn init() -> Ssd1305Controller {
-    let mut controller = setup();
-
-    reset(&mut controller);
-
-    controller.command(0xAE); //--turn off oled panel
-    controller.command(0x04); //--turn off oled panel
-    controller.command(0x10); //--turn off oled panel
-    controller.command(0x40); //---set low column address
-    controller.command(0x81); //---set high column address
-    controller.command(0x80); //--set start line address  Set Mapping RAM Display Start Line (0x00~0x3F)
-    controller.command(0xA1); //--set contrast control register
-    controller.command(0xA6); // Set SEG Output Current Brightness
-    controller.command(0xA8); //--Set SEG/Column Mapping     0xa0×óÓÒ·´ÖÃ 0xa1Õý³£
-    controller.command(0x1F); //Set COM/Row Scan Direction   0xc0ÉÏÏÂ·´ÖÃ 0xc8Õý³£
-    controller.command(0xC8); //--set normal display
-    controller.command(0xD3); //--set multiplex ratio(1 to 64)
-    controller.command(0x00); //--1/64 duty
-    controller.command(0xD5); //-set display offset	Shift Mapping RAM Counter (0x00~0x3F)
-    controller.command(0xF0); //-not offset
-    controller.command(0xd8); //--set display clock divide ratio/oscillator frequency
-    controller.command(0x05); //--set divide ratio, Set Clock as 100 Frames/Sec
-    controller.command(0xD9); //--set pre-charge period
-    controller.command(0xC2); //Set Pre-Charge as 15 Clocks & Discharge as 1 Clock
-    controller.command(0xDA); //--set com pins hardware configuration
-    controller.command(0x12);
-    controller.command(0xDB); //--set vcomh
-    controller.command(0x08); //Set VCOM Deselect Level
-    controller.command(0xAF); //-Set Page Addressing Mode (0x00/0x01/0x02)
-
-    set_pixel(&mut controller.buffer, 0, 0, 1);
-    set_pixel(&mut controller.buffer, 127, 0, 1);
-    set_pixel(&mut controller.buffer, 0, 31, 1);
-    set_pixel(&mut controller.buffer, 127, 31, 1);
-
-    controller.display();
-
-    controller
-}
+    #[test]
+    fn test_init_sequence() {
+        let bus = MockBus::default();
+        let mut controller = controller(&bus);
+
+        controller.init().unwrap();
+
+        let events = bus.events();
+        assert_eq!(
+            &events[0..3],
+            &[Event::Reset(true), Event::Reset(false), Event::Reset(true)]
+        );
+        assert_eq!(events[3], Event::Command(0xAE), "Panel off first");
+
+        let commands = bus.commands();
+        //Multiplex ratio follows 0xA8 and matches the panel height
+        let multiplex = commands.iter().position(|&c| c == 0xA8).unwrap();
+        assert_eq!(commands[multiplex + 1], 31);
+        assert_eq!(commands[23], 0xAF, "Panel on last");
+
+        //Then the whole buffer with the corners lit
+        let data = bus.data();
+        assert_eq!(data.len(), 512);
+        assert_eq!(data[0], 0b0000_0001);
+        assert_eq!(data[127], 0b0000_0001);
+        assert_eq!(data[384], 0b1000_0000);
+        assert_eq!(data[511], 0b1000_0000);
+        assert_eq!(data.iter().filter(|&&b| b != 0).count(), 4);
+    }
 
-fn reset(controller: &mut Ssd1305Controller) {
-    controller.gpio_rst.write(gpio::Level::High);
-    thread::sleep(Duration::from_millis(10));
-    controller.gpio_rst.write(gpio::Level::Low);
-    thread::sleep(Duration::from_millis(10));
-    controller.gpio_rst.write(gpio::Level::High);
-}
+    #[test]
+    fn test_page_writes() {
+        let bus = MockBus::default();
+        let mut controller = controller(&bus);
+        controller.init().unwrap();
+        bus.clear();
+
+        let mut frame = MonoFrame::new(128, 32);
+        frame.set_pixel(0, 0, true);
+        frame.set_pixel(20, 10, true);
+        controller.load_frame(&frame);
+        controller.display().unwrap();
+
+        //The corners except the top left went out, the pixel at 20, 10 came on
+        assert_eq!(
+            bus.events(),
+            vec![
+                Event::Command(0xB0),
+                Event::Command(0x03),
+                Event::Command(0x18),
+                Event::Data(vec![0]),
+                Event::Command(0xB1),
+                Event::Command(0x08),
+                Event::Command(0x11),
+                Event::Data(vec![0b0000_0100]),
+                Event::Command(0xB3),
+                Event::Command(0x04),
+                Event::Command(0x10),
+                Event::Data(vec![0; 128]),
+            ]
+        );
+    }
 
-fn set_pixel(buffer: &mut [u8; 512], x: usize, y: usize, color: u8) {
-    if x >= WIDTH || y >= HEIGHT {
-        println!("SetColor: Pixel out of bounds x:{} y:{}", x, y);
-        return;
+    #[test]
+    fn test_bus_errors_are_returned() {
+        let bus = MockBus::default();
+        let mut controller = controller(&bus);
+        controller.init().unwrap();
+
+        bus.fail_writes(true);
+        controller.set_pixel(3, 3, 1);
+        assert!(matches!(controller.display(), Err(DriverError::Bus(_))));
+        assert!(matches!(controller.init(), Err(DriverError::Bus(_))));
+
+        //Once the bus is back, init brings the panel back with a full redraw
+        bus.fail_writes(false);
+        bus.clear();
+        controller.init().unwrap();
+        assert_eq!(bus.data().len(), 512);
     }
 
-    //The 128x32 display is split into 4 strips of 128x8,
-    //where each column of 8 is encoded into a single value
+    #[test]
+    fn test_display_commands() {
+        let bus = MockBus::default();
+        let mut controller = controller(&bus);
+        controller.init().unwrap();
+        bus.clear();
+
+        controller.set_contrast(0x20).unwrap();
+        controller.set_inverted(true).unwrap();
+        controller.set_display_on(false).unwrap();
+        assert_eq!(bus.commands(), vec![0x81, 0x20, 0xA7, 0xAE]);
+
+        //Rotating flips both scan directions and redraws with the other column offset
+        bus.clear();
+        controller.set_rotated(true).unwrap();
+        assert_eq!(&bus.commands()[0..5], &[0xA0, 0xC0, 0xB0, 0x00, 0x10]);
+        assert_eq!(bus.data().len(), 512);
+
+        //A reset keeps the settings
+        bus.clear();
+        controller.init().unwrap();
+        let commands = bus.commands();
+        for expected in &[0x20, 0xA0, 0xA7, 0xC0, 0xAE] {
+            assert!(commands[0..24].contains(expected));
+        }
+        assert!(!commands[0..24].contains(&0xAF));
+    }
 
-    if color > 0 {
-        buffer[x + (y / 8) * WIDTH] |= 1 << (y % 8);
-    } else {
-        buffer[x + (y / 8) * WIDTH] &= !(1 << (y % 8));
+    #[test]
+    fn test_hardware_scroll() {
+        let bus = MockBus::default();
+        let mut controller = controller(&bus);
+        controller.init().unwrap();
+        bus.clear();
+
+        assert!(controller.can_scroll());
+        controller.start_scroll().unwrap();
+        //Stop, set up all 4 pages to scroll left through the segment remap, start
+        assert_eq!(
+            bus.commands(),
+            vec![0x2E, 0x26, 0x01, 0x00, 0b100, 0x03, 0x2F]
+        );
+        assert!(bus.data().is_empty(), "The panel scrolls on its own");
+
+        //Stopping redraws the frame the scroll moved around
+        bus.clear();
+        controller.stop_scroll().unwrap();
+        assert_eq!(bus.commands()[0], 0x2E);
+        assert_eq!(bus.data().len(), 512);
+
+        //The remap is flipped on a rotated panel, so is the direction
+        controller.set_rotated(true).unwrap();
+        bus.clear();
+        controller.start_scroll().unwrap();
+        assert_eq!(bus.commands()[1], 0x27);
     }
 }
@@ -0,0 +1,107 @@
+use crate::oled::{Chip, PanelState, SCROLL_INTERVAL};
+
+// The common 0.96" and 0.91" breakout panels, 128x64 or 128x32
+pub struct Ssd1306;
+
+impl Chip for Ssd1306 {
+    fn init_sequence(&self, height: u32, state: &PanelState) -> Vec<u8> {
+        vec![
+            0xAE, //Display off
+            0xD5, //Set display clock divide ratio/oscillator frequency
+            0x80, //Reset value
+            0xA8, //Set multiplex ratio
+            (height - 1) as u8,
+            0xD3, //Set display offset
+            0x00,
+            0x40, //Set display start line to 0
+            0x8D, //Charge pump setting
+            0x14, //Enable, the panels don't have an external supply
+            0x20, //Set memory addressing mode
+            0x02, //Page addressing, the reset value but it may have been changed
+            state.segment_remap(),
+            state.com_scan_direction(),
+            0xDA, //Set COM pins hardware configuration
+            com_pins(height),
+            0x81, //Set contrast control
+            state.contrast,
+            0xD9, //Set pre-charge period
+            0xF1,
+            0xDB, //Set VCOMH deselect level
+            0x40,
+            0xA4, //Show the RAM contents
+            state.display_mode(),
+            state.display_power(),
+        ]
+    }
+
+    //128 columns of display RAM, all of them visible
+    fn column_offset(&self, _rotated: bool) -> u8 {
+        0
+    }
+
+    //The step is always one column on the SSD1306
+    fn scroll_setup(&self, direction: u8, end_page: u8) -> Option<Vec<u8>> {
+        Some(vec![
+            direction,
+            0x00, //Dummy byte
+            0x00, //Start page
+            SCROLL_INTERVAL,
+            end_page,
+            0x00, //Dummy bytes
+            0xFF,
+        ])
+    }
+}
+
+// 32 row panels wire the COM pins sequentially, 64 row panels alternate them
+fn com_pins(height: u32) -> u8 {
+    match height {
+        0..=32 => 0x02,
+        _ => 0x12,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_hal::{MockBus, MockPin, MockSpi};
+    use crate::oled::{OledController, SpiTransport};
+
+    fn controller(
+        bus: &MockBus,
+    ) -> OledController<Ssd1306, SpiTransport<MockSpi, MockPin>, MockPin> {
+        OledController::new(
+            Ssd1306,
+            SpiTransport::new(bus.spi(), bus.dc()),
+            Some(bus.rst()),
+            128,
+            64,
+        )
+    }
+
+    #[test]
+    fn test_init_sequence() {
+        let bus = MockBus::default();
+        controller(&bus).init().unwrap();
+
+        let commands = bus.commands();
+        let multiplex = commands.iter().position(|&c| c == 0xA8).unwrap();
+        assert_eq!(commands[multiplex + 1], 63);
+        let com_pins = commands.iter().position(|&c| c == 0xDA).unwrap();
+        assert_eq!(commands[com_pins + 1], 0x12);
+        assert!(commands.windows(2).any(|w| w == [0x8D, 0x14]));
+
+        //Page writes start at column 0, over 8 pages
+        assert_eq!(&commands[25..28], &[0xB0, 0x00, 0x10]);
+        assert_eq!(bus.data().len(), 1024);
+    }
+
+    #[test]
+    fn test_com_pins_follow_height() {
+        let state = PanelState::default();
+
+        let sequence = Ssd1306.init_sequence(32, &state);
+        let com_pins = sequence.iter().position(|&c| c == 0xDA).unwrap();
+        assert_eq!(sequence[com_pins + 1], 0x02);
+    }
+}
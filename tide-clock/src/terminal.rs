@@ -0,0 +1,142 @@
+use crate::display::{DisplayControl, EmulatedControls, RenderDevice, DEFAULT_CONTRAST};
+use crate::mono_frame::MonoFrame;
+use std::error::Error;
+use std::io::{self, Stdout, Write};
+
+//ANSI escapes
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+// Draws frames in the terminal, two rows of pixels per line of half block characters, in a
+// box so the edges of the panel show. Each frame is drawn over the last one, so it works
+// over SSH and on the Pi with the panel unplugged
+pub struct TerminalWriter<W: Write = Stdout> {
+    width: u32,
+    height: u32,
+    out: W,
+    //Lines the last frame took up, to move the cursor back over
+    drawn_lines: usize,
+    controls: EmulatedControls,
+}
+
+impl TerminalWriter<Stdout> {
+    pub fn new(width: u32, height: u32) -> TerminalWriter<Stdout> {
+        TerminalWriter::with_writer(width, height, io::stdout())
+    }
+}
+
+impl<W: Write> TerminalWriter<W> {
+    pub fn with_writer(width: u32, height: u32, out: W) -> TerminalWriter<W> {
+        TerminalWriter {
+            width,
+            height,
+            out,
+            drawn_lines: 0,
+            controls: EmulatedControls::default(),
+        }
+    }
+}
+
+// The frame as lines of text, border included
+fn draw(frame: &MonoFrame) -> Vec<String> {
+    let (width, height) = frame.dimensions();
+    let border = "─".repeat(width as usize);
+
+    let mut lines = vec![format!("┌{}┐", border)];
+    for top in (0..height).step_by(2) {
+        let row: String = (0..width)
+            .map(|x| {
+                let upper = frame.get_pixel(x, top);
+                let lower = top + 1 < height && frame.get_pixel(x, top + 1);
+                match (upper, lower) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                }
+            })
+            .collect();
+        lines.push(format!("│{}│", row));
+    }
+    lines.push(format!("└{}┘", border));
+
+    lines
+}
+
+impl<W: Write> RenderDevice for TerminalWriter<W> {
+    fn render(&mut self, buffer: &MonoFrame) -> Result<(), Box<dyn Error>> {
+        let lines = draw(&self.controls.apply(buffer));
+
+        let mut text = String::new();
+        if self.drawn_lines > 0 {
+            //Cursor up to where the last frame started
+            text.push_str(&format!("\x1b[{}A", self.drawn_lines));
+        }
+        //Terminals can't show the contrast, dim is the closest there is
+        let dimmed = self.controls.contrast < DEFAULT_CONTRAST;
+        for line in &lines {
+            match dimmed {
+                true => text.push_str(&format!("\r{}{}{}\n", DIM, line, RESET)),
+                false => text.push_str(&format!("\r{}\n", line)),
+            }
+        }
+
+        self.out.write_all(text.as_bytes())?;
+        self.out.flush()?;
+        self.drawn_lines = lines.len();
+
+        Ok(())
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn controls(&mut self) -> Option<&mut dyn DisplayControl> {
+        Some(&mut self.controls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(writer: &TerminalWriter<Vec<u8>>) -> String {
+        String::from_utf8(writer.out.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_half_blocks() {
+        let frame = MonoFrame::from_fn(4, 3, |x, y| (x == 0 && y == 0) || x == 1 || y == 1);
+
+        assert_eq!(draw(&frame), vec!["┌────┐", "│██▄▄│", "│ ▀  │", "└────┘"]);
+    }
+
+    #[test]
+    fn test_frames_are_drawn_in_place() {
+        let mut writer = TerminalWriter::with_writer(128, 32, Vec::new());
+        let frame = MonoFrame::new(128, 32);
+
+        writer.render(&frame).unwrap();
+        let first = output(&writer);
+        assert_eq!(first.lines().count(), 18);
+        assert!(!first.contains('\x1b'));
+
+        //The second frame starts by going back up over the first
+        writer.out.clear();
+        writer.render(&frame).unwrap();
+        assert!(output(&writer).starts_with("\x1b[18A\r┌"));
+    }
+
+    #[test]
+    fn test_controls() {
+        let mut writer = TerminalWriter::with_writer(2, 2, Vec::new());
+        let controls = writer.controls().unwrap();
+        controls.set_inverted(true).unwrap();
+        controls.set_contrast(0x10).unwrap();
+
+        writer.render(&MonoFrame::new(2, 2)).unwrap();
+
+        assert!(output(&writer).contains(&format!("{}│██│{}", DIM, RESET)));
+    }
+}
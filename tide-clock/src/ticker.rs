@@ -0,0 +1,209 @@
+use crate::display::RenderDevice;
+use crate::font::Font5;
+use crate::mono_frame::MonoFrame;
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+
+//Time per column when scrolling in software, close to the panels' 3 frame scroll interval
+const STEP: Duration = Duration::from_millis(30);
+
+//Blank columns between the end of the text and the start of it coming round again
+const GAP: u32 = 16;
+
+// Scrolls `text` right to left across the display for `duration`, wrapping around like a
+// marquee. Panels that can scroll by themselves get a single frame when the text fits on the
+// screen, which the start up messages do on a 128 wide panel. Everything else, including any
+// text wider than the screen, gets a new frame per column
+pub fn play(
+    device: &mut dyn RenderDevice,
+    font: &Font5,
+    text: &str,
+    duration: Duration,
+) -> Result<(), Box<dyn Error>> {
+    println!("{}", text);
+
+    let (width, height) = device.size();
+    let tape = tape(font, text, width, height);
+
+    //Hardware scroll wraps the panel's display RAM, which holds one screen's width (the 4
+    //spare columns on the SSD1305 aren't worth the trouble), so longer text is done in software
+    if tape.width() == width && device.scroller().is_some() {
+        return scroll_in_hardware(device, &tape, duration);
+    }
+
+    let start = Instant::now();
+    let mut offset = 0;
+    while start.elapsed() < duration {
+        device.render(&window(&tape, width, offset))?;
+        offset = (offset + 1) % tape.width();
+        thread::sleep(STEP);
+    }
+
+    Ok(())
+}
+
+fn scroll_in_hardware(
+    device: &mut dyn RenderDevice,
+    tape: &MonoFrame,
+    duration: Duration,
+) -> Result<(), Box<dyn Error>> {
+    device.render(tape)?;
+
+    let scroller = device.scroller().expect("Checked by the caller");
+    scroller.start_scroll()?;
+    thread::sleep(duration);
+    scroller.stop_scroll()
+}
+
+// The text on one line, vertically centered. Centered on the screen too when it fits,
+// otherwise as wide as it needs to be with a gap before it repeats
+fn tape(font: &Font5, text: &str, width: u32, height: u32) -> MonoFrame {
+    let glyphs: Vec<&MonoFrame> = text.chars().filter_map(|c| font.faces.get(&c)).collect();
+    let text_width: u32 = glyphs.iter().map(|g| g.width() + 1).sum();
+
+    let mut tape = MonoFrame::new(width.max(text_width + GAP), height);
+
+    let mut caret = match tape.width() == width {
+        true => (width / 2).saturating_sub(text_width / 2),
+        false => 0,
+    };
+    let top = (height / 2).saturating_sub(3);
+    for glyph in glyphs {
+        tape.blit(glyph, caret, top);
+        caret += glyph.width() + 1;
+    }
+
+    tape
+}
+
+// What's on screen `offset` columns into the scroll
+fn window(tape: &MonoFrame, width: u32, offset: u32) -> MonoFrame {
+    MonoFrame::from_fn(width, tape.height(), |x, y| {
+        tape.get_pixel((x + offset) % tape.width(), y)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app;
+    use crate::font;
+    use crate::mock_hal::{Event, MockBus};
+    use crate::oled::{OledController, SpiTransport};
+    use crate::ssd1305::Ssd1305;
+
+    // Keeps every frame it's given
+    struct RecordingDevice {
+        frames: Vec<MonoFrame>,
+    }
+
+    impl RenderDevice for RecordingDevice {
+        fn render(&mut self, buffer: &MonoFrame) -> Result<(), Box<dyn Error>> {
+            self.frames.push(buffer.clone());
+            Ok(())
+        }
+
+        fn size(&self) -> (u32, u32) {
+            (128, 32)
+        }
+    }
+
+    #[test]
+    fn test_software_scroll() {
+        let font = font::init();
+        let mut device = RecordingDevice { frames: vec![] };
+
+        play(&mut device, &font, "HI", Duration::from_millis(100)).unwrap();
+
+        //One column further each frame, wrapping round
+        let frames = &device.frames;
+        assert!(frames.len() >= 2);
+        assert_eq!(frames[1], frames[0].shifted(-1, 0));
+    }
+
+    #[test]
+    fn test_long_text_scrolls_through() {
+        let font = font::init();
+        let text = "YOU LOOK MAGNIFICENT TODAY AND TOMORROW";
+        let tape = tape(&font, text, 128, 32);
+
+        assert!(tape.width() > 128);
+        //Starts at the left edge, the end of the text comes into view later on
+        let (left, _, right, _) = tape.lit_bounds().unwrap();
+        assert_eq!(left, 0);
+        assert!(right >= 128);
+        let later = window(&tape, 128, right - 100);
+        assert!((0..32).any(|y| later.get_pixel(100, y)));
+        assert_eq!(window(&tape, 128, tape.width()), window(&tape, 128, 0));
+    }
+
+    // A 128x32 SSD1305 on a mock bus, initialised with the bus log cleared
+    fn panel() -> (impl RenderDevice, MockBus) {
+        let bus = MockBus::default();
+        let transport = SpiTransport::new(bus.spi(), bus.dc());
+        let mut device = OledController::new(Ssd1305, transport, Some(bus.rst()), 128, 32);
+        device.init().unwrap();
+        bus.clear();
+
+        (device, bus)
+    }
+
+    fn started_scroll(bus: &MockBus) -> bool {
+        bus.events().contains(&Event::Command(0x2F))
+    }
+
+    #[test]
+    fn test_hardware_scroll() {
+        let font = font::init();
+        let (mut device, bus) = panel();
+
+        play(&mut device, &font, "HI", Duration::from_millis(10)).unwrap();
+
+        //The frame once, then nothing while the panel scrolls until it's stopped and redrawn
+        let events = bus.events();
+        let start = events
+            .iter()
+            .position(|e| *e == Event::Command(0x2F))
+            .unwrap();
+        assert!(events[..start].iter().any(|e| matches!(e, Event::Data(_))));
+        assert_eq!(events[start + 1], Event::Command(0x2E));
+        let redrawn: usize = events[start..]
+            .iter()
+            .map(|e| match e {
+                Event::Data(d) => d.len(),
+                _ => 0,
+            })
+            .sum();
+        assert_eq!(redrawn, 512);
+    }
+
+    #[test]
+    fn test_splash_scrolls_in_hardware() {
+        let font = font::init();
+        assert_eq!(tape(&font, app::SPLASH, 128, 32).width(), 128);
+
+        let (mut device, bus) = panel();
+        play(&mut device, &font, app::SPLASH, Duration::from_millis(10)).unwrap();
+
+        assert!(started_scroll(&bus));
+    }
+
+    #[test]
+    fn test_wide_text_scrolls_in_software() {
+        let font = font::init();
+        let (mut device, bus) = panel();
+
+        let text = "YOU LOOK MAGNIFICENT TODAY AND TOMORROW";
+        play(&mut device, &font, text, Duration::from_millis(100)).unwrap();
+
+        //Frame after frame over the bus instead
+        assert!(!started_scroll(&bus));
+        let writes = bus
+            .events()
+            .iter()
+            .filter(|e| matches!(e, Event::Data(_)))
+            .count();
+        assert!(writes > 2);
+    }
+}
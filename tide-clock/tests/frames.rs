@@ -0,0 +1,31 @@
+// Whole frames through the public API, the way the binary composes them
+use tide_clock::layout::{self, Page};
+use tide_clock::snapshot::{assert_snapshot, with_fixture_ctx};
+use tide_clock::{app, font, MonoFrame};
+
+fn compose_at(name: &str, time: &str) {
+    let img = with_fixture_ctx(time, |ctx| layout::compose(&Page::default(), ctx));
+
+    assert_snapshot(name, &img);
+}
+
+#[test]
+fn test_compose_frame() {
+    compose_at("frame_start_of_data", "2020-10-02T19:00:00Z");
+    compose_at("frame_mid_window", "2020-10-03T02:31:00Z");
+}
+
+#[test]
+fn test_compose_frame_colon_blink() {
+    compose_at("frame_colon_blink", "2020-10-02T19:00:01Z");
+}
+
+#[test]
+fn test_render_splash() {
+    let font = font::init();
+    let mut img: MonoFrame = MonoFrame::new(128, 32);
+
+    app::render("YOU LOOK MAGNIFICENT TODAY", &font, &mut img);
+
+    assert_snapshot("splash", &img);
+}
@@ -0,0 +1,112 @@
+// The painters one at a time against golden images, through the public API
+use chrono::{Offset, Utc};
+use tide_clock::display::{ExtremeLabel, GraphCanvas, Position, TextField, TextList, WaterMark};
+use tide_clock::snapshot::{assert_snapshot, fixture_model, fixture_time};
+use tide_clock::{font, MonoFrame, Painter};
+
+const SCREEN_WIDTH: u32 = 128;
+const SCREEN_HEIGHT: u32 = 32;
+
+#[test]
+fn test_text_field_snapshot() {
+    let font = font::init();
+    let mut img = MonoFrame::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+
+    let mut text = TextField::new("00:00".to_string(), &font, 0, 0);
+    text.set_text("12_34".to_string());
+    text.paint(&mut img, fixture_time("2020-10-02T19:00:00Z"));
+
+    let height = TextField::new("1.8m".to_string(), &font, 0, 27);
+    height.paint(&mut img, fixture_time("2020-10-02T19:00:00Z"));
+
+    assert_snapshot("text_field", &img);
+}
+
+#[test]
+fn test_graph_canvas_snapshot() {
+    let font = font::init();
+    let model = fixture_model();
+
+    for (name, time) in &[
+        ("graph_canvas_start", "2020-10-02T19:00:00Z"),
+        ("graph_canvas_later", "2020-10-03T09:12:00Z"),
+    ] {
+        let now = fixture_time(time);
+        let (window, _) = model.get_window(now, 107);
+        let mut img = MonoFrame::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+
+        GraphCanvas::new(21, 10, 107, 22, window, &font, Utc.fix()).paint(&mut img, now);
+
+        assert_snapshot(name, &img);
+    }
+}
+
+#[test]
+fn test_graph_canvas_on_taller_screen() {
+    let font = font::init();
+    let model = fixture_model();
+    let now = fixture_time("2020-10-02T19:00:00Z");
+    let (window, _) = model.get_window(now, 107);
+
+    //The play head stops at the bottom of the graph, not the screen
+    let mut img = MonoFrame::new(SCREEN_WIDTH, 64);
+    GraphCanvas::new(21, 10, 107, 22, window, &font, Utc.fix()).paint(&mut img, now);
+
+    assert_snapshot("graph_canvas_64_rows", &img);
+}
+
+#[test]
+fn test_extreme_label_snapshot() {
+    let font = font::init();
+    let model = fixture_model();
+    let now = fixture_time("2020-10-02T19:00:00Z");
+    let (window, _) = model.get_window(now, 107);
+    let mut img = MonoFrame::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+
+    // Fill the lower half with "water" so the descender has something to stop at
+    for y in 20..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            img.set_pixel(x, y, true);
+        }
+    }
+    for y in 16..20 {
+        for x in 0..SCREEN_WIDTH {
+            img.set_pixel(x, y, false);
+        }
+    }
+
+    let canvas_pos = Position { x: 21, y: 10 };
+    let extreme = &window.extremes()[0];
+    let index = window.get_extreme_index_in_window(extreme.index());
+    ExtremeLabel::new(&font, extreme, index, &canvas_pos, Utc.fix()).paint(&mut img, now);
+
+    assert_snapshot("extreme_label", &img);
+}
+
+#[test]
+fn test_water_mark_snapshot() {
+    let model = fixture_model();
+
+    for (name, time) in &[
+        ("water_mark_low", "2020-10-02T13:28:00Z"),
+        ("water_mark_high", "2020-10-02T16:24:00Z"),
+        ("water_mark_outside_data", "2021-01-01T00:00:00Z"),
+    ] {
+        let mut img = MonoFrame::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+
+        WaterMark::new(17, 10, 2, 22, &model).paint(&mut img, fixture_time(time));
+
+        assert_snapshot(name, &img);
+    }
+}
+
+#[test]
+fn test_text_list_snapshot() {
+    let font = font::init();
+    let mut img = MonoFrame::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+
+    let lines = vec!["HIGH 16:24 1.8m".to_string(), "LOW 18:30 1.8m".to_string()];
+    TextList::new(lines, &font, 2, 3, 8).paint(&mut img, fixture_time("2020-10-02T19:00:00Z"));
+
+    assert_snapshot("text_list", &img);
+}